@@ -0,0 +1,53 @@
+//! Nonce search throughput of the original sha3 loop against each lane width this cpu supports.
+//! `cargo bench --bench keccak`
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use sha3::{Digest, Keccak256};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::keccak::{hash, Hash};
+
+#[allow(dead_code)]
+#[path = "../src/keccak.rs"]
+mod keccak;
+
+use keccak::{Lanes, Midstate};
+
+/// Nonces per iteration
+const NONCES: u64 = 1 << 14;
+
+/// The nonce loop `Miner::mine` used before the lane search
+fn sha3_loop(prefix: &[u8; 64], difficulty: &Hash, end: u64) -> Option<(u64, Hash)> {
+    let mut hasher = Keccak256::default();
+    for nonce in 0..end {
+        hasher.update(prefix.as_slice());
+        hasher.update(nonce.to_le_bytes().as_slice());
+        let next_hash = Hash(<[u8; 32]>::try_from(hasher.finalize_reset().as_slice()).unwrap());
+        if next_hash.le(difficulty) {
+            return Some((nonce, next_hash));
+        }
+    }
+    None
+}
+
+fn search(c: &mut Criterion) {
+    let prefix = keccak::prefix(&hash(b"bench"), &Pubkey::new_unique());
+    let midstate = Midstate::new(&prefix);
+    // nothing is found, so every nonce of the range is hashed
+    let difficulty = Hash::new_from_array([0; 32]);
+
+    let mut group = c.benchmark_group("search");
+    group.throughput(Throughput::Elements(NONCES));
+    group.bench_function("sha3 loop", |b| b.iter(|| sha3_loop(&prefix, &difficulty, NONCES)));
+    let widest = Lanes::detect().width();
+    for lanes in [Lanes::Scalar, Lanes::Avx2, Lanes::Avx512] {
+        if lanes.width() <= widest {
+            group.bench_function(BenchmarkId::new("lanes", format!("{:?}", lanes)), |b| {
+                b.iter(|| lanes.search(&midstate, &difficulty, 0..NONCES))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, search);
+criterion_main!(benches);
@@ -3,3 +3,8 @@ use anchor_lang::solana_program::{pubkey, pubkey::Pubkey};
 pub const ORE_PROGRAM_ID:                Pubkey = pubkey!("mineRHF5r6S7HyD9SppBfVMXMavDkJsxwGesEvxZr2A");
 pub const MINER_COLLECTIVE_TREASURY:     Pubkey = pubkey!("omc1vcb6CmMywXcDxyL77VaPYU98WyyaP3Mx6LBuaTr");
 pub const MINER_COLLECTIVE_ORE_TREASURY: Pubkey = pubkey!("9idoAEtTrcnoXmrSYMx3pQQYiRLPND3NvcgJnfk6oihW");
+
+/// Lamports sent to the miner to cover the ore proof account rent
+pub const PROOF_RENT: u64 = 2_600_000;
+/// Lamports paid to the collective treasury per registered miner
+pub const REGISTRATION_FEE: u64 = 10_000_000;
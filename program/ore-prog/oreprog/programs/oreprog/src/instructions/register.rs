@@ -42,46 +42,115 @@ pub struct Register<'info> {
     system_program: Program<'info, System>,
 }
 
+/// Same as Register, but a separate fee payer covers the proof rent and the registration fee,
+/// so the authority doesn't need to hold any SOL.
+#[derive(Accounts)]
+#[instruction(id: u8)]
+pub struct RegisterWithPayer<'info> {
+    
+    ///CHECK: ok
+    #[account(mut, seeds = [b"x", authority.key().as_ref(), &[id]], bump)]
+    miner: UncheckedAccount<'info>,
+
+    ///CHECK: ok
+    #[account(mut)]
+    proof: UncheckedAccount<'info>,
+
+    authority: Signer<'info>,
+
+    #[account(mut)]
+    payer: Signer<'info>,
+
+    ///CHECK: ok
+    #[account(mut, address = MINER_COLLECTIVE_TREASURY)]
+    miner_collective_treasury: UncheckedAccount<'info>,
+
+    ///CHECK: ok
+    #[account(address = ORE_PROGRAM_ID, executable)]
+    ore: Program<'info, Ore>,
+
+    system_program: Program<'info, System>,
+}
+
 impl Register<'_> {
     pub fn handle(ctx: Context<Register>, id: u8) -> Result<()> {
-        let register_ix = ore::instruction::register(ctx.accounts.miner.key());
-        let authority = &ctx.accounts.authority.key();
-        let signer_seeds: &[&[&[u8]]] = &[&[
-            b"x", 
-            authority.as_ref(), 
-            &[id], 
-            &[ctx.bumps.miner]
-        ]];
-        let cpi_context = CpiContext::new(
-            ctx.accounts.system_program.to_account_info(), 
-            system_program::Transfer {
-                from: ctx.accounts.authority.to_account_info().clone(),
-                to: ctx.accounts.miner.to_account_info().clone(),
-        });
-
-        // cover ore account rent 0.001_559_000 sol
-        system_program::transfer(cpi_context, 2_600_000)?; 
-
-        invoke_signed(
-            &register_ix, 
-            &[
-                ctx.accounts.miner.to_account_info(),
-                ctx.accounts.proof.to_account_info(),
-                ctx.accounts.system_program.to_account_info(),
-            ], 
-            signer_seeds)?;
-        //return err!(OreCollectiveError::AnError);
-
-        let cpi_context = CpiContext::new(
-            ctx.accounts.system_program.to_account_info(), 
-            system_program::Transfer {
-                from: ctx.accounts.authority.to_account_info().clone(),
-                to: ctx.accounts.miner_collective_treasury.to_account_info().clone(),
-        });
-
-        // .01 sol registration cost per account
-        system_program::transfer(cpi_context, 10_000_000)?; 
-
-        Ok(())
+        let accounts = &ctx.accounts;
+        register(
+            id,
+            ctx.bumps.miner,
+            &accounts.authority.key(),
+            accounts.authority.to_account_info(),
+            accounts.miner.to_account_info(),
+            accounts.proof.to_account_info(),
+            accounts.miner_collective_treasury.to_account_info(),
+            accounts.system_program.to_account_info(),
+        )
+    }
+}
+
+impl RegisterWithPayer<'_> {
+    pub fn handle(ctx: Context<RegisterWithPayer>, id: u8) -> Result<()> {
+        let accounts = &ctx.accounts;
+        register(
+            id,
+            ctx.bumps.miner,
+            &accounts.authority.key(),
+            accounts.payer.to_account_info(),
+            accounts.miner.to_account_info(),
+            accounts.proof.to_account_info(),
+            accounts.miner_collective_treasury.to_account_info(),
+            accounts.system_program.to_account_info(),
+        )
     }
 }
+
+/// Fund the proof rent from `payer`, register the miner pda with ore and pay the registration fee
+fn register<'info>(
+    id: u8,
+    bump: u8,
+    authority: &Pubkey,
+    payer: AccountInfo<'info>,
+    miner: AccountInfo<'info>,
+    proof: AccountInfo<'info>,
+    miner_collective_treasury: AccountInfo<'info>,
+    system_program: AccountInfo<'info>,
+) -> Result<()> {
+    let register_ix = ore::instruction::register(miner.key());
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        b"x", 
+        authority.as_ref(), 
+        &[id], 
+        &[bump]
+    ]];
+    let cpi_context = CpiContext::new(
+        system_program.clone(), 
+        system_program::Transfer {
+            from: payer.clone(),
+            to: miner.clone(),
+    });
+
+    // cover ore account rent 0.001_559_000 sol
+    system_program::transfer(cpi_context, PROOF_RENT)?; 
+
+    invoke_signed(
+        &register_ix, 
+        &[
+            miner,
+            proof,
+            system_program.clone(),
+        ], 
+        signer_seeds)?;
+    //return err!(OreCollectiveError::AnError);
+
+    let cpi_context = CpiContext::new(
+        system_program, 
+        system_program::Transfer {
+            from: payer,
+            to: miner_collective_treasury,
+    });
+
+    // .01 sol registration cost per account
+    system_program::transfer(cpi_context, REGISTRATION_FEE)?; 
+
+    Ok(())
+}
@@ -20,6 +20,10 @@ pub mod oreprog {
         Register::handle(ctx, id)
     }
 
+    pub fn register_with_payer(ctx: Context<RegisterWithPayer>, id: u8) -> Result<()> {
+        RegisterWithPayer::handle(ctx, id)
+    }
+
     //pub fn mine(ctx: Context<Mine>, ids: Vec<IndexedSolution>) -> Result<()> {
     pub fn mine<'info>(ctx: Context<'_, '_, '_, 'info, Mine<'info>>, ids: Vec<IndexedSolution>) -> Result<()> {
         // msg!("{:?}", ctx.accounts.slot_hashes);
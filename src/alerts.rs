@@ -0,0 +1,199 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    process::Command,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use log::Level;
+use serde::Serialize;
+use solana_program::native_token::LAMPORTS_PER_SOL;
+
+use crate::{
+    config::{ALERT_COMMAND, ALERT_COOLDOWN, ALERT_INTERVAL, ALERT_RULES, ALERT_WEBHOOK, ALERT_WINDOW},
+    stats::{notify, Activity, Stats},
+};
+
+/// A condition on `Stats` that should wake somebody up
+#[derive(Debug, Clone, Copy)]
+pub enum AlertRule {
+    /// funding wallet balance below this many lamports
+    LowBalance(u64),
+    /// no combo confirmed for this many seconds
+    NoLanding(u64),
+    /// share of signatures that failed or expired over the last ALERT_WINDOW seconds
+    /// is above this ratio, once at least 10 were sent
+    FailureRatio(f64),
+    /// a thread has been mining, accumulating or sending the same batch for this many seconds.
+    /// Idle and paused threads are not considered stuck.
+    ThreadStuck(u64),
+}
+
+impl AlertRule {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AlertRule::LowBalance(_) => "low_balance",
+            AlertRule::NoLanding(_) => "no_landing",
+            AlertRule::FailureRatio(_) => "failure_ratio",
+            AlertRule::ThreadStuck(_) => "thread_stuck",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Alert<'a> {
+    rule: &'a str,
+    /// false once the condition cleared
+    firing: bool,
+    message: &'a str,
+    /// unix timestamp
+    time: i64,
+}
+
+/// What the checker remembers between evaluations
+struct Tracker {
+    started: Instant,
+    /// `landed_combos` and when it last changed
+    landed: (u64, Instant),
+    /// (time, sent_sigs, failed_sigs) over the last ALERT_WINDOW seconds
+    sigs: VecDeque<(Instant, u64, u64)>,
+    /// per thread: (activity, batch) and since when
+    threads: Vec<((&'static str, usize), Instant)>,
+    /// rules currently firing, by index in ALERT_RULES, and when they were last sent out
+    firing: HashMap<usize, Instant>,
+}
+
+impl Tracker {
+    fn new() -> Self {
+        let now = Instant::now();
+        Tracker {
+            started: now,
+            landed: (0, now),
+            sigs: VecDeque::new(),
+            threads: vec![],
+            firing: HashMap::new(),
+        }
+    }
+
+    /// Take a snapshot of `stats` and return the message of every rule that currently holds,
+    /// with the rule's index in ALERT_RULES
+    fn evaluate(&mut self, stats: &Stats) -> Vec<(usize, AlertRule, Option<String>)> {
+        let now = Instant::now();
+        if stats.landed_combos != self.landed.0 {
+            self.landed = (stats.landed_combos, now);
+        }
+        self.sigs.push_back((now, stats.sent_sigs, stats.failed_sigs));
+        while self.sigs.len() > 1 && now.duration_since(self.sigs[0].0) > Duration::from_secs(ALERT_WINDOW) {
+            self.sigs.pop_front();
+        }
+        self.threads.resize(stats.threads.len(), (("idle", 0), now));
+        for (status, tracked) in stats.threads.iter().zip(self.threads.iter_mut()) {
+            let activity = (status.activity.name(), status.activity.batch());
+            if tracked.0 != activity {
+                *tracked = (activity, now);
+            }
+        }
+
+        ALERT_RULES.iter().enumerate().map(|(index, rule)| {
+            let message = match *rule {
+                // balance is only known once the price bot has run
+                AlertRule::LowBalance(min) => (stats.balance > 0 && stats.balance < min).then(|| {
+                    format!("funding wallet balance {:.4} SOL is below {:.4} SOL",
+                        stats.balance as f64 / LAMPORTS_PER_SOL as f64,
+                        min as f64 / LAMPORTS_PER_SOL as f64)
+                }),
+                AlertRule::NoLanding(secs) => {
+                    let since = now.duration_since(self.landed.1).as_secs();
+                    (since >= secs).then(|| if self.landed.1 == self.started {
+                        format!("no combo confirmed since start {}s ago", since)
+                    } else {
+                        format!("no combo confirmed for {}s", since)
+                    })
+                }
+                AlertRule::FailureRatio(max) => {
+                    let (_, sent0, failed0) = self.sigs[0];
+                    let sent = stats.sent_sigs - sent0;
+                    let failed = stats.failed_sigs - failed0;
+                    let ratio = failed as f64 / sent.max(1) as f64;
+                    (sent >= 10 && ratio > max).then(|| {
+                        format!("{} of {} signatures failed in the last {}s ({:.0}%)", failed, sent, ALERT_WINDOW, ratio * 100.0)
+                    })
+                }
+                AlertRule::ThreadStuck(secs) => {
+                    let stuck = stats.threads.iter().zip(self.threads.iter())
+                        .filter(|(status, _)| !matches!(status.activity, Activity::Idle | Activity::Paused(_)))
+                        .map(|(status, (_, since))| (status, now.duration_since(*since).as_secs()))
+                        .filter(|(_, elapsed)| *elapsed >= secs)
+                        .map(|(status, elapsed)| format!("{:?} thread {} {} for {}s", status.ttype, status.id, status.activity.name(), elapsed))
+                        .collect::<Vec<_>>();
+                    (!stuck.is_empty()).then(|| stuck.join(", "))
+                }
+            };
+            (index, *rule, message)
+        }).collect()
+    }
+}
+
+/// Send an alert to the webhook and the local command, whichever are configured
+async fn fire(client: &reqwest::Client, alert: &Alert<'_>, stats: &Mutex<Stats>) {
+    if let Some(url) = ALERT_WEBHOOK {
+        if let Err(err) = client.post(url).json(alert).send().await.and_then(|res| res.error_for_status()) {
+            notify!(stats.lock().unwrap(), Level::Warn, "[alert] webhook failed: {}", err);
+        }
+    }
+    if let Some(command) = ALERT_COMMAND {
+        let result = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("OGRE_ALERT", alert.rule)
+            .env("OGRE_ALERT_FIRING", alert.firing.to_string())
+            .env("OGRE_ALERT_MESSAGE", alert.message)
+            .status();
+        match result {
+            Ok(status) if status.success() => {}
+            Ok(status) => notify!(stats.lock().unwrap(), Level::Warn, "[alert] command exited with {}", status),
+            Err(err) => notify!(stats.lock().unwrap(), Level::Warn, "[alert] command failed: {}", err),
+        }
+    }
+}
+
+/// Evaluate ALERT_RULES every ALERT_INTERVAL seconds on a background thread.
+/// A firing rule is sent again at most every ALERT_COOLDOWN seconds, and once more when it clears.
+pub fn spawn(stats: Arc<Mutex<Stats>>) {
+    if ALERT_RULES.is_empty() {
+        return;
+    }
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let client = reqwest::Client::new();
+            let mut tracker = Tracker::new();
+            loop {
+                tokio::time::sleep(Duration::from_secs(ALERT_INTERVAL)).await;
+                let results = tracker.evaluate(&stats.lock().unwrap());
+                for (index, rule, message) in results {
+                    let now = Instant::now();
+                    let (firing, message) = match (message, tracker.firing.get(&index)) {
+                        (Some(_), Some(last)) if now.duration_since(*last) < Duration::from_secs(ALERT_COOLDOWN) => continue,
+                        (Some(message), _) => {
+                            tracker.firing.insert(index, now);
+                            (true, message)
+                        }
+                        (None, Some(_)) => {
+                            tracker.firing.remove(&index);
+                            (false, "resolved".to_string())
+                        }
+                        (None, None) => continue,
+                    };
+                    let level = if firing { Level::Warn } else { Level::Info };
+                    notify!(stats.lock().unwrap(), level, "[alert] {}: {}", rule.name(), message);
+                    let alert = Alert { rule: rule.name(), firing, message: &message, time: chrono::Utc::now().timestamp() };
+                    fire(&client, &alert, &stats).await;
+                }
+            }
+        });
+    });
+}
@@ -0,0 +1,121 @@
+use std::{net::ToSocketAddrs, sync::{Arc, Mutex}, thread};
+
+use log::Level;
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{claim::spawn_claim_all, config::{API_TOKEN, MAX_TIP, MIN_TIP}, stats::{notify, Stats}};
+
+#[derive(Deserialize)]
+struct SetValue {
+    value: u64,
+}
+
+#[derive(Serialize)]
+struct Message<'a> {
+    ok: bool,
+    message: &'a str,
+}
+
+/// Serve the json status and control api on `addr` from a background thread.
+///
+/// GET  /stats, /threads, /miners, /notifications
+/// POST /pause, /resume, /claim, /tip {"value": lamports}, /priority-fee {"value": microlamports}
+///
+/// POSTs need `Authorization: Bearer <API_TOKEN>` when a token is set. Without a token only
+/// loopback addresses are served.
+pub fn serve(addr: &str, stats: Arc<Mutex<Stats>>) {
+    if API_TOKEN.is_none() && !loopback(addr) {
+        let mut stats = stats.lock().unwrap();
+        notify!(stats, Level::Error, "[api] not serving {} without API_TOKEN, it isn't a loopback address", addr);
+        return;
+    }
+    let server = match Server::http(addr) {
+        Ok(server) => server,
+        Err(err) => {
+            let mut stats = stats.lock().unwrap();
+            notify!(stats, Level::Error, "[api] failed to bind {}: {:?}", addr, err);
+            return;
+        }
+    };
+    thread::spawn(move || {
+        for mut request in server.incoming_requests() {
+            let (status, body) = handle(&mut request, &stats);
+            let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+            request.respond(Response::from_string(body).with_status_code(status).with_header(header)).ok();
+        }
+    });
+}
+
+/// Whether every address `addr` resolves to is loopback
+fn loopback(addr: &str) -> bool {
+    addr.to_socket_addrs().is_ok_and(|mut addrs| addrs.all(|a| a.ip().is_loopback()))
+}
+
+/// Whether the request carries API_TOKEN, or no token is configured
+fn authorized(request: &Request) -> bool {
+    let Some(token) = API_TOKEN else {
+        return true;
+    };
+    let expected = format!("Bearer {}", token);
+    request.headers().iter()
+        .find(|h| h.field.equiv("Authorization"))
+        .is_some_and(|h| {
+            let given = h.value.as_str().as_bytes();
+            // compare without an early exit, so timing doesn't reveal the matching prefix
+            given.len() == expected.len() && given.iter().zip(expected.as_bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+        })
+}
+
+fn message(ok: bool, message: &str) -> String {
+    serde_json::to_string(&Message { ok, message }).unwrap()
+}
+
+fn read_value(request: &mut Request) -> Option<u64> {
+    serde_json::from_reader::<_, SetValue>(request.as_reader()).ok().map(|v| v.value)
+}
+
+fn handle(request: &mut Request, stats: &Arc<Mutex<Stats>>) -> (u16, String) {
+    let url = request.url().to_string();
+    if *request.method() == Method::Post && !authorized(request) {
+        return (401, message(false, "unauthorized"));
+    }
+    match (request.method(), url.as_str()) {
+        (Method::Get, "/stats") => (200, serde_json::to_string(&*stats.lock().unwrap()).unwrap()),
+        (Method::Get, "/threads") => (200, serde_json::to_string(&stats.lock().unwrap().threads).unwrap()),
+        (Method::Get, "/miners") => {
+            let stats = stats.lock().unwrap();
+            let miners = stats.miner_status.values().collect::<Vec<_>>();
+            (200, serde_json::to_string(&miners).unwrap())
+        }
+        (Method::Get, "/notifications") => (200, serde_json::to_string(&stats.lock().unwrap().notifications).unwrap()),
+        (Method::Post, "/pause") => {
+            stats.lock().unwrap().paused = true;
+            (200, message(true, "submission paused"))
+        }
+        (Method::Post, "/resume") => {
+            stats.lock().unwrap().paused = false;
+            (200, message(true, "submission resumed"))
+        }
+        (Method::Post, "/tip") => match read_value(request) {
+            Some(value) => {
+                let tip = value.clamp(MIN_TIP, MAX_TIP);
+                stats.lock().unwrap().tip = tip;
+                (200, message(true, &format!("tip set to {}", tip)))
+            }
+            None => (400, message(false, "expected {\"value\": lamports}")),
+        },
+        (Method::Post, "/priority-fee") => match read_value(request) {
+            Some(value) => {
+                stats.lock().unwrap().priority_fee = value;
+                (200, message(true, "priority fee updated"))
+            }
+            None => (400, message(false, "expected {\"value\": microlamports}")),
+        },
+        (Method::Post, "/claim") => {
+            spawn_claim_all(stats.clone());
+            (202, message(true, "claim started"))
+        }
+        _ => (404, message(false, "not found")),
+    }
+}
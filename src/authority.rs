@@ -0,0 +1,69 @@
+use cached::proc_macro::cached;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+
+use crate::{
+    config::{ALT, AUTHORITIES, FEE_PAYER, FUNDING, MINER_COUNT},
+    keystore::read_keypair,
+    utils::pair_pubkey,
+};
+
+/// A keypair owning a set of miners, configured in AUTHORITIES
+#[derive(Debug, Clone, Copy)]
+pub struct Authority {
+    pub keypair: &'static str,
+    /// lookup table holding this authority's miner and proof accounts
+    pub alt: Pubkey,
+    /// number of miners, with ids 0..miners
+    pub miners: u8,
+    /// spending limits for this authority's transactions, on top of BUDGET_HOURLY and BUDGET_DAILY
+    pub budget_hourly: Option<u64>,
+    pub budget_daily: Option<u64>,
+}
+
+/// FUNDING with ALT and MINER_COUNT, used when AUTHORITIES is empty
+const DEFAULT: &[Authority] = &[Authority {
+    keypair: FUNDING,
+    alt: ALT,
+    miners: MINER_COUNT,
+    budget_hourly: None,
+    budget_daily: None,
+}];
+
+/// All configured authorities
+pub fn all() -> &'static [Authority] {
+    if AUTHORITIES.is_empty() {
+        DEFAULT
+    } else {
+        AUTHORITIES
+    }
+}
+
+#[cached]
+fn keypair_pubkey(path: &'static str) -> Pubkey {
+    pair_pubkey(&read_keypair(path).unwrap())
+}
+
+impl Authority {
+    pub fn pubkey(&self) -> Pubkey {
+        keypair_pubkey(self.keypair)
+    }
+
+    pub fn read_keypair(&self) -> Keypair {
+        read_keypair(self.keypair).unwrap()
+    }
+
+    /// Keypair paying this authority's fees: FEE_PAYER if set, the authority itself otherwise
+    pub fn read_fee_payer(&self) -> Keypair {
+        read_keypair(FEE_PAYER.unwrap_or(self.keypair)).unwrap()
+    }
+
+    pub fn fee_payer(&self) -> Pubkey {
+        keypair_pubkey(FEE_PAYER.unwrap_or(self.keypair))
+    }
+}
+
+/// The configured authority with this pubkey
+pub fn find(pubkey: &Pubkey) -> &'static Authority {
+    all().iter().find(|a| a.pubkey() == *pubkey).expect("miner of an unknown authority")
+}
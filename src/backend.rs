@@ -0,0 +1,200 @@
+use std::{
+    fmt::Debug,
+    io::Write,
+    ops::Range,
+    process::{Command, Stdio},
+    sync::{atomic::{AtomicBool, Ordering}, Mutex},
+};
+
+use oreprog::IndexedSolution;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::keccak::{hash, hashv, Hash};
+
+use crate::keccak::{prefix, Lanes, Midstate};
+
+/// Something that finds nonces: for a miner's midstate, laid out from its 64 byte
+/// `challenge || miner` prefix, the first nonce in a range whose keccak hash of `prefix || nonce`
+/// is at most the difficulty
+pub trait HashBackend: Debug + Send + Sync {
+    fn name(&self) -> String;
+
+    fn search(&self, midstate: &Midstate, difficulty: &Hash, nonces: Range<u64>) -> Option<(u64, Hash)>;
+
+    /// Nonces handed to `search` at a time by `search_split`, between looks at the cancel flag
+    fn chunk(&self) -> u64 {
+        1 << 16
+    }
+
+    /// Check that found nonces hash like the on-chain `IndexedSolution::get_hash`
+    fn self_check(&self) -> Result<(), String> {
+        check_search(self)
+    }
+}
+
+/// Search a few synthetic miners at an easy difficulty and verify what's found
+fn check_search<B: HashBackend + ?Sized>(backend: &B) -> Result<(), String> {
+    let difficulty = Hash::new_from_array([0x0f; 32]);
+    for seed in 0u8..4 {
+        let challenge = hash(&[seed]);
+        let miner = Pubkey::new_unique();
+        let (nonce, found) = backend.search(&Midstate::new(&prefix(&challenge, &miner)), &difficulty, 0..1 << 20)
+            .ok_or("no nonce found")?;
+        let expected = IndexedSolution { id: 0, bump: 0, nonce }.get_hash(miner, challenge);
+        if found.0 != expected.0 || !found.le(&difficulty) {
+            return Err(format!("nonce {} hashes to {}, expected {}", nonce, found, Hash(expected.0)));
+        }
+    }
+    Ok(())
+}
+
+/// One nonce at a time
+#[derive(Debug)]
+pub struct Scalar;
+
+impl HashBackend for Scalar {
+    fn name(&self) -> String {
+        "scalar".to_string()
+    }
+
+    fn search(&self, midstate: &Midstate, difficulty: &Hash, nonces: Range<u64>) -> Option<(u64, Hash)> {
+        Lanes::Scalar.search(midstate, difficulty, nonces)
+    }
+
+    fn self_check(&self) -> Result<(), String> {
+        Lanes::Scalar.self_check().and_then(|_| check_search(self))
+    }
+}
+
+/// Several nonces per permutation in vector registers
+#[derive(Debug)]
+pub struct Simd(pub Lanes);
+
+impl HashBackend for Simd {
+    fn name(&self) -> String {
+        format!("{:?} ({} lanes)", self.0, self.0.width()).to_lowercase()
+    }
+
+    fn search(&self, midstate: &Midstate, difficulty: &Hash, nonces: Range<u64>) -> Option<(u64, Hash)> {
+        self.0.search(midstate, difficulty, nonces)
+    }
+
+    fn self_check(&self) -> Result<(), String> {
+        if !self.0.supported() {
+            return Err(format!("this cpu doesn't support {:?}", self.0));
+        }
+        self.0.self_check().and_then(|_| check_search(self))
+    }
+}
+
+/// A program run through `sh -c` for every chunk, e.g. a GPU miner. It gets one line on stdin,
+/// `<prefix hex> <difficulty hex> <first nonce> <end nonce>`, and prints the nonce it found, or
+/// nothing. Found nonces are verified before use.
+#[derive(Debug)]
+pub struct External(pub &'static str);
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl HashBackend for External {
+    fn name(&self) -> String {
+        format!("external `{}`", self.0)
+    }
+
+    fn search(&self, midstate: &Midstate, difficulty: &Hash, nonces: Range<u64>) -> Option<(u64, Hash)> {
+        let prefix = midstate.prefix();
+        let mut child = Command::new("sh")
+            .args(["-c", self.0])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|err| log::error!("hash backend `{}` failed to start: {}", self.0, err))
+            .ok()?;
+        let request = format!("{} {} {} {}\n", hex(&prefix), hex(difficulty.as_ref()), nonces.start, nonces.end);
+        if let Err(err) = child.stdin.take()?.write_all(request.as_bytes()) {
+            log::error!("hash backend `{}`: {}", self.0, err);
+        }
+        let output = child.wait_with_output().ok()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let nonce = stdout.trim();
+        if nonce.is_empty() {
+            return None;
+        }
+        let Ok(nonce) = nonce.parse::<u64>() else {
+            log::error!("hash backend `{}` answered {:?}, not a nonce", self.0, nonce);
+            return None;
+        };
+        let found = hashv(&[prefix.as_slice(), nonce.to_le_bytes().as_slice()]);
+        if !nonces.contains(&nonce) || !found.le(difficulty) {
+            log::error!("hash backend `{}` answered nonce {} which isn't a solution", self.0, nonce);
+            return None;
+        }
+        Some((nonce, found))
+    }
+
+    /// External programs are slow to start, give them larger ranges
+    fn chunk(&self) -> u64 {
+        1 << 32
+    }
+}
+
+/// Which HashBackend to mine with, set in HASH_BACKEND
+#[derive(Debug, Clone, Copy)]
+pub enum Backend {
+    /// The widest SIMD this cpu supports, scalar without any
+    Auto,
+    Scalar,
+    /// A fixed lane width, which the cpu has to support
+    Simd(Lanes),
+    /// Only ever set in config
+    #[allow(dead_code)]
+    External(&'static str),
+}
+
+impl Backend {
+    pub fn backend(&self) -> Box<dyn HashBackend> {
+        match *self {
+            Backend::Auto => match Lanes::detect() {
+                Lanes::Scalar => Backend::Scalar,
+                lanes => Backend::Simd(lanes),
+            }.backend(),
+            Backend::Scalar | Backend::Simd(Lanes::Scalar) => Box::new(Scalar),
+            Backend::Simd(lanes) => Box::new(Simd(lanes)),
+            Backend::External(command) => Box::new(External(command)),
+        }
+    }
+}
+
+/// Search one miner's nonces on `workers` threads taking turns on chunks of the nonce space.
+/// The first to find a nonce stops the others; raising `cancel` stops all of them and returns None.
+pub fn search_split(backend: &dyn HashBackend, midstate: &Midstate, difficulty: &Hash, workers: usize, cancel: &AtomicBool) -> Option<(u64, Hash)> {
+    let workers = workers.max(1) as u64;
+    let chunk = backend.chunk();
+    let done = AtomicBool::new(false);
+    let found = Mutex::new(None);
+    let worker = |w: u64| {
+        let mut start = w * chunk;
+        while !done.load(Ordering::Relaxed) && !cancel.load(Ordering::Relaxed) {
+            if let Some(solution) = backend.search(midstate, difficulty, start..start.saturating_add(chunk)) {
+                if !done.swap(true, Ordering::Relaxed) {
+                    *found.lock().unwrap() = Some(solution);
+                }
+                return;
+            }
+            match start.checked_add(workers * chunk) {
+                Some(next) => start = next,
+                None => return,
+            }
+        }
+    };
+    if workers == 1 {
+        worker(0);
+    } else {
+        std::thread::scope(|s| {
+            for w in 0..workers {
+                s.spawn(move || worker(w));
+            }
+        });
+    }
+    found.into_inner().unwrap()
+}
@@ -0,0 +1,192 @@
+use std::{
+    error::Error,
+    str::FromStr,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use comfy_table::{presets::UTF8_BORDERS_ONLY, Attribute, Cell, Table};
+use ore::{state::Treasury, utils::AccountDeserialize, TREASURY_ADDRESS};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{commitment_config::CommitmentConfig, keccak::{hash, Hash}};
+
+use crate::{
+    backend::{Backend, HashBackend},
+    config::{HASH_BACKEND, RPC, SEARCH_WORKERS},
+    keccak::{self, Lanes, Midstate},
+};
+
+/// Thread counts within this share of the fastest count the same
+const RECOMMEND_SHARE: f64 = 0.95;
+/// Nonces in a thread's first search call
+const FIRST_CHUNK: u64 = 1 << 12;
+
+#[derive(clap::Args, Debug)]
+pub struct BenchArgs {
+    /// Difficulty to search against, base58. Defaults to the treasury's current difficulty.
+    #[arg(long)]
+    pub difficulty: Option<String>,
+    /// Thread counts to try, comma separated. Defaults to powers of two up to the cpu count.
+    #[arg(long, value_delimiter = ',')]
+    pub threads: Vec<usize>,
+    /// Seconds to run each backend and thread count for
+    #[arg(long, default_value_t = 3)]
+    pub seconds: u64,
+}
+
+/// Throughput of one backend at one thread count
+struct Run {
+    backend: Backend,
+    name: String,
+    threads: usize,
+    hashes_per_second: f64,
+}
+
+/// Every backend this machine can run: scalar, each SIMD width the cpu supports and the
+/// configured external program
+fn backends() -> Vec<Backend> {
+    let mut backends = vec![Backend::Scalar];
+    for lanes in [Lanes::Avx2, Lanes::Avx512] {
+        if lanes.supported() {
+            backends.push(Backend::Simd(lanes));
+        }
+    }
+    if let Backend::External(_) = HASH_BACKEND {
+        backends.push(HASH_BACKEND);
+    }
+    backends
+}
+
+/// How to set `backend` as HASH_BACKEND in config.rs
+fn config_value(backend: &Backend) -> String {
+    match backend {
+        Backend::Auto => "Backend::Auto".to_string(),
+        Backend::Scalar => "Backend::Scalar".to_string(),
+        Backend::Simd(lanes) => format!("Backend::Simd(Lanes::{:?})", lanes),
+        Backend::External(command) => format!("Backend::External({:?})", command),
+    }
+}
+
+fn default_threads() -> Vec<usize> {
+    let cpus = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let mut threads = (0..).map(|i| 1 << i).take_while(|&n| n < cpus).collect::<Vec<_>>();
+    threads.push(cpus);
+    threads
+}
+
+async fn current_difficulty() -> Result<Hash, Box<dyn Error>> {
+    let client = RpcClient::new_with_commitment(RPC.to_string(), CommitmentConfig::confirmed());
+    let data = client.get_account_data(&TREASURY_ADDRESS).await?;
+    let treasury = Treasury::try_from_bytes(&data)?;
+    Ok(treasury.difficulty.into())
+}
+
+/// Chance of a single hash being at most `difficulty`
+fn solution_chance(difficulty: &Hash) -> f64 {
+    difficulty.as_ref().iter().rev().fold(0.0, |chance, &byte| (chance + byte as f64) / 256.0)
+}
+
+/// Search with `threads` threads for `duration`, counting every nonce hashed. A found nonce
+/// doesn't end a thread's search, it carries on past it like the next miner would.
+/// Chunks start small and double up to the backend's chunk while one takes less than a twentieth
+/// of `duration`, so a slow backend with a huge chunk can't overrun the measurement.
+fn measure(backend: &dyn HashBackend, difficulty: &Hash, threads: usize, duration: Duration) -> f64 {
+    let hashes = AtomicU64::new(0);
+    let stop = AtomicBool::new(false);
+    let started = Instant::now();
+    std::thread::scope(|s| {
+        for t in 0..threads {
+            let (hashes, stop) = (&hashes, &stop);
+            s.spawn(move || {
+                let midstate = Midstate::new(&keccak::prefix(&hash(b"ogre bench"), &Pubkey::new_unique()));
+                let mut start = (t as u64) << 40;
+                let mut chunk = FIRST_CHUNK.min(backend.chunk());
+                while !stop.load(Ordering::Relaxed) {
+                    let end = start + chunk;
+                    let searched = Instant::now();
+                    let next = match backend.search(&midstate, difficulty, start..end) {
+                        Some((nonce, _)) => nonce + 1,
+                        None => end,
+                    };
+                    if searched.elapsed() < duration / 20 {
+                        chunk = (chunk * 2).min(backend.chunk());
+                    }
+                    hashes.fetch_add(next - start, Ordering::Relaxed);
+                    start = next;
+                }
+            });
+        }
+        std::thread::sleep(duration);
+        stop.store(true, Ordering::Relaxed);
+    });
+    hashes.into_inner() as f64 / started.elapsed().as_secs_f64()
+}
+
+fn table(runs: &[Run], chance: f64) -> Table {
+    let mut table = Table::new();
+    table.load_preset(UTF8_BORDERS_ONLY);
+    table.set_header(vec![
+        Cell::new("Backend").add_attribute(Attribute::Bold),
+        Cell::new("Threads").add_attribute(Attribute::Bold),
+        Cell::new("Hashes/s").add_attribute(Attribute::Bold),
+        Cell::new("Per thread").add_attribute(Attribute::Bold),
+        Cell::new("Secs/solution").add_attribute(Attribute::Bold),
+        Cell::new("Solutions/h").add_attribute(Attribute::Bold),
+    ]);
+    for run in runs {
+        let per_thread = run.hashes_per_second / run.threads as f64;
+        // one OgreThread mines one miner at a time, split across SEARCH_WORKERS threads
+        let per_miner = per_thread * SEARCH_WORKERS.clamp(1, run.threads) as f64;
+        table.add_row(vec![
+            Cell::new(&run.name),
+            Cell::new(format!("{}", run.threads)),
+            Cell::new(format!("{:.0}", run.hashes_per_second)),
+            Cell::new(format!("{:.0}", per_thread)),
+            Cell::new(format!("{:.2}", 1.0 / (chance * per_miner))),
+            Cell::new(format!("{:.1}", chance * run.hashes_per_second * 3600.0)),
+        ]);
+    }
+    table
+}
+
+pub async fn run(args: BenchArgs) -> Result<(), Box<dyn Error>> {
+    let difficulty = match &args.difficulty {
+        Some(difficulty) => Hash::from_str(difficulty).map_err(|err| format!("invalid difficulty {}: {}", difficulty, err))?,
+        None => current_difficulty().await.map_err(|err| format!("can't read the current difficulty, pass --difficulty: {}", err))?,
+    };
+    let threads = if args.threads.is_empty() { default_threads() } else { args.threads };
+    let chance = solution_chance(&difficulty);
+    println!("Difficulty {}, 1 in {:.0} hashes is a solution", difficulty, 1.0 / chance);
+
+    let mut runs = vec![];
+    for config in backends() {
+        let backend = config.backend();
+        if let Err(err) = backend.self_check() {
+            println!("Skipping {}: {}", backend.name(), err);
+            continue;
+        }
+        for &n in threads.iter().filter(|&&n| n > 0) {
+            let hashes_per_second = measure(backend.as_ref(), &difficulty, n, Duration::from_secs(args.seconds));
+            println!("{} with {} threads: {:.0} hashes/s", backend.name(), n, hashes_per_second);
+            runs.push(Run { backend: config, name: backend.name(), threads: n, hashes_per_second });
+        }
+    }
+    println!("{}", table(&runs, chance));
+
+    let Some(best) = runs.iter().max_by(|a, b| a.hashes_per_second.total_cmp(&b.hashes_per_second)) else {
+        return Err("nothing was measured".into());
+    };
+    let recommended = runs.iter()
+        .filter(|run| run.name == best.name && run.hashes_per_second >= best.hashes_per_second * RECOMMEND_SHARE)
+        .min_by_key(|run| run.threads)
+        .unwrap_or(best);
+    // every OgreThread runs SEARCH_WORKERS threads
+    let ogrethreads = (recommended.threads / SEARCH_WORKERS.max(1)).max(1);
+    println!(
+        "Recommended: {} with {} threads, {:.0} hashes/s. Set HASH_BACKEND = {} and OGRETHREADS = {}; with SEARCH_WORKERS = {} that is {} hashing threads in total.",
+        recommended.name, recommended.threads, recommended.hashes_per_second, config_value(&recommended.backend),
+        ogrethreads, SEARCH_WORKERS, ogrethreads * SEARCH_WORKERS.max(1),
+    );
+    Ok(())
+}
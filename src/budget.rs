@@ -0,0 +1,93 @@
+use oreprog::constants::{PROOF_RENT, REGISTRATION_FEE};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{CU_LIMIT_MINE, CU_LIMIT_REGISTER, CU_LIMIT_TRANSFER, INCLUDE_TIP, LAMPORTS_PER_SIGNATURE},
+    miner::{Miner, MinerState},
+    stats::Stats,
+};
+
+const HOUR: i64 = 60 * 60;
+const DAY: i64 = 24 * HOUR;
+
+/// Lamports spent in the current UTC hour and day, checked against hourly and daily limits
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct Budget {
+    /// unix timestamp the current hour started
+    pub hour: i64,
+    pub hour_spent: u64,
+    /// unix timestamp the current day started
+    pub day: i64,
+    pub day_spent: u64,
+    /// most that transactions in flight can still cost, counted against both windows until
+    /// they land or expire. Not restored, nothing is in flight after a restart.
+    #[serde(skip_deserializing)]
+    pub reserved: u64,
+}
+
+impl Budget {
+    /// Start new windows once the hour or day is over
+    fn roll(&mut self, now: i64) {
+        if now - self.hour >= HOUR {
+            self.hour = now - now.rem_euclid(HOUR);
+            self.hour_spent = 0;
+        }
+        if now - self.day >= DAY {
+            self.day = now - now.rem_euclid(DAY);
+            self.day_spent = 0;
+        }
+    }
+
+    pub fn spend(&mut self, lamports: u64, now: i64) {
+        self.roll(now);
+        self.hour_spent += lamports;
+        self.day_spent += lamports;
+    }
+
+    pub fn reserve(&mut self, lamports: u64) {
+        self.reserved += lamports;
+    }
+
+    pub fn release(&mut self, lamports: u64) {
+        self.reserved = self.reserved.saturating_sub(lamports);
+    }
+
+    /// Lamports left this hour and this day, None where no limit is set
+    pub fn remaining(&self, now: i64, hourly: Option<u64>, daily: Option<u64>) -> (Option<u64>, Option<u64>) {
+        let mut budget = *self;
+        budget.roll(now);
+        (
+            hourly.map(|b| b.saturating_sub(budget.hour_spent + budget.reserved)),
+            daily.map(|b| b.saturating_sub(budget.day_spent + budget.reserved)),
+        )
+    }
+
+    /// If spending `lamports` now would exceed a limit, the unix timestamp that limit resets
+    pub fn exceeded_until(&self, lamports: u64, now: i64, hourly: Option<u64>, daily: Option<u64>) -> Option<i64> {
+        let (hourly, daily) = self.remaining(now, hourly, daily);
+        if daily.is_some_and(|left| lamports > left) {
+            Some(now - now.rem_euclid(DAY) + DAY)
+        } else if hourly.is_some_and(|left| lamports > left) {
+            Some(now - now.rem_euclid(HOUR) + HOUR)
+        } else {
+            None
+        }
+    }
+
+    /// Whether `lamports` exceeds a limit itself, so it wouldn't fit even an empty window
+    pub fn exceeds_limit(lamports: u64, hourly: Option<u64>, daily: Option<u64>) -> bool {
+        hourly.is_some_and(|b| lamports > b) || daily.is_some_and(|b| lamports > b)
+    }
+}
+
+/// The most a transaction for `miners` can cost if it lands, at the current tip and priority fee
+pub fn max_cost(miners: &[Miner], stats: &Stats) -> u64 {
+    let registrations = miners.iter().filter(|m| matches!(m.state, MinerState::Premined(..))).count() as u64;
+    let mut cu = CU_LIMIT_MINE as u64 * miners.len() as u64 + CU_LIMIT_REGISTER as u64 * registrations;
+    let mut lamports = LAMPORTS_PER_SIGNATURE + registrations * (REGISTRATION_FEE + PROOF_RENT);
+    if INCLUDE_TIP {
+        cu += CU_LIMIT_TRANSFER as u64;
+        lamports += stats.tip;
+    }
+    lamports + stats.priority_fee * cu / 1_000_000
+}
@@ -0,0 +1,110 @@
+use std::sync::{Arc, Mutex};
+
+use log::Level;
+use oreprog::constants::ORE_PROGRAM_ID;
+use solana_client::{client_error::ClientError, nonblocking::rpc_client::RpcClient};
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    compute_budget::ComputeBudgetInstruction,
+    signature::Signature,
+};
+use spl_associated_token_account::{get_associated_token_address, instruction::create_associated_token_account_idempotent};
+
+use crate::{
+    authority,
+    config::{CLAIM_BATCH, RPC, CU_LIMIT_CLAIM, JITO, MINT, ORE_COLLECTIVE_ORE_TREASURY, ORE_TREASURY, ORE_TREASURY_TOKENS},
+    stats::{notify, Stats},
+    utils::{claim_ix, create_tx_with_address_table_lookup, get_state, miner_pubkey, pair_pubkey, proof_pubkey, signers},
+};
+
+/// Claim instructions for the first `miners` miners of `authority` with claimable rewards, in
+/// transaction sized batches. Rewards go to the authority's ore token account, created by `payer`
+/// if missing.
+pub async fn claim_instructions(rpc: &str, authority: Pubkey, miners: u8, payer: Pubkey, priority_fee: u64) -> Result<Vec<Vec<Instruction>>, ClientError> {
+    let beneficiary = get_associated_token_address(&authority, &MINT);
+    let mut claims = vec![];
+    for id in 0..miners {
+        let (miner, _bump) = miner_pubkey(authority, id);
+        let Some(proof) = get_state(rpc.to_string(), &miner).await? else {
+            continue;
+        };
+        if proof.claimable_rewards == 0 {
+            continue;
+        }
+        claims.push(claim_ix(
+            oreprog::accounts::Claim {
+                authority,
+                beneficiary,
+                miner,
+                proof: proof_pubkey(miner),
+                treasury: ORE_TREASURY,
+                treasury_tokens: ORE_TREASURY_TOKENS,
+                miner_collective_ore_treasury: ORE_COLLECTIVE_ORE_TREASURY,
+                ore: ORE_PROGRAM_ID,
+                token_program: spl_token::id(),
+            }, proof.claimable_rewards, id));
+    }
+
+    Ok(claims.chunks(CLAIM_BATCH).map(|chunk| {
+        let mut ixs = vec![
+            ComputeBudgetInstruction::set_compute_unit_limit(CU_LIMIT_CLAIM * chunk.len() as u32 + 30_000),
+            ComputeBudgetInstruction::set_compute_unit_price(priority_fee),
+            create_associated_token_account_idempotent(&payer, &authority, &MINT, &spl_token::id()),
+        ];
+        ixs.extend_from_slice(chunk);
+        ixs
+    }).collect())
+}
+
+/// Claim all rewards of every authority's miners. Returns the signatures sent.
+pub async fn claim_all(rpc: &str, stats: Arc<Mutex<Stats>>) -> Vec<Signature> {
+    let priority_fee = stats.lock().unwrap().priority_fee;
+    let client = RpcClient::new_with_commitment(rpc.to_string(), CommitmentConfig::confirmed());
+    let jito = RpcClient::new_with_commitment(JITO.to_string(), CommitmentConfig::confirmed());
+
+    let mut sigs = vec![];
+    for authority in authority::all() {
+        let keypair = authority.read_keypair();
+        let payer = authority.read_fee_payer();
+        let signers = signers(&keypair, &payer);
+        let claims = match claim_instructions(rpc, authority.pubkey(), authority.miners, pair_pubkey(&payer), priority_fee).await {
+            Ok(claims) => claims,
+            Err(err) => {
+                notify!(stats.lock().unwrap(), Level::Error, "[claim] can't read the proofs of {}: {:?}", authority.pubkey(), err);
+                continue;
+            }
+        };
+        for ixs in claims {
+            let result = match create_tx_with_address_table_lookup(&client, &ixs, authority.alt, &payer, &signers).await {
+                Ok((tx, send_cfg)) => jito.send_transaction_with_config(&tx, send_cfg).await,
+                Err(err) => Err(err),
+            };
+            let mut stats = stats.lock().unwrap();
+            match result {
+                Ok(sig) => {
+                    notify!(stats, Level::Info, "[claim] Sent claim for {} miners of {} {}", ixs.len() - 3, authority.pubkey(), sig);
+                    sigs.push(sig);
+                }
+                Err(err) => notify!(stats, Level::Error, "[claim] {:?}", err),
+            }
+        }
+    }
+    if sigs.is_empty() {
+        notify!(stats.lock().unwrap(), Level::Info, "[claim] Nothing claimed");
+    }
+    sigs
+}
+
+/// Run `claim_all` on a background thread
+pub fn spawn_claim_all(stats: Arc<Mutex<Stats>>) {
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            claim_all(RPC, stats).await;
+        });
+    });
+}
@@ -1,29 +1,49 @@
 use solana_program::{pubkey, pubkey::Pubkey};
 
+use crate::{alerts::AlertRule, authority::Authority, backend::Backend, oracle::PriceSource, profit::ProfitPolicy, tip::TipStrategyKind};
+
 /// Threads running the hashing/mining algorithm
 pub const OGRETHREADS: u32 = 8;
+/// Threads each OgreThread splits a single miner's nonce search across, always, so
+/// OGRETHREADS * SEARCH_WORKERS threads hash in total; keep that at the cpu count. With fewer
+/// minable miners than OGRETHREADS, fewer OgreThreads with more workers each finish solutions sooner.
+pub const SEARCH_WORKERS: usize = 1;
+/// What hashes nonces: `Backend::Auto` for the widest SIMD this cpu has, `Backend::Scalar`,
+/// `Backend::Simd(Lanes::Avx2)` or `Backend::Simd(Lanes::Avx512)` for a fixed width (with
+/// `crate::keccak::Lanes` imported), or `Backend::External("./gpu-search")` running a program
+/// for every chunk of nonces.
+/// `ogre bench` measures them and recommends one.
+pub const HASH_BACKEND: Backend = Backend::Auto;
 /// Threads sending and confirming transactions
 pub const SUBMITTERTHREADS: u32 = 3;
 
-/// Keypair
+/// Keypair, a plaintext keypair file or a keystore made with `ogre keys`
 pub const FUNDING: &str = "./id.json";
-/// Pubkey of above
-pub const FUNDING_PK: Pubkey = pubkey!("PUBKEYPUBKEYPUBKEYPUBKEY");
+/// Optional separate keypair paying transaction fees, tips and registrations. FUNDING stays the
+/// authority owning the miners and their rewards, but then doesn't need to hold SOL.
+/// Registering with a separate payer needs the collective program's register_with_payer instruction.
+pub const FEE_PAYER: Option<&str> = None;
 
 /// RPC used for reading Data from the chain
 pub const RPC: &str = "https://api.mainnet-beta.solana.com";
+/// Genesis hash of the cluster RPC must be on, mainnet-beta
+pub const GENESIS_HASH: &str = "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d";
 /// RPC used for sendTransaction only. It's called jito but doesn't have to be jito at all.
 pub const JITO: &str = "https://api.mainnet-beta.solana.com";
 /// If sendTransaction RPC above is jito, set this to true to add a tip
 pub const INCLUDE_TIP: bool = false;
 /// Initial Tip amount. Will automatically increase/decrease depending on if your txs are landing
 pub const TIP: u64 = 50_001;
-/// Deprecated
+/// Bounds for tip strategies that compute the tip from scratch
+pub const MIN_TIP: u64 = 1_000;
 pub const MAX_TIP: u64 = 500_000;
+/// How the tip follows landing success, see TipStrategyKind
+pub const TIP_STRATEGY: TipStrategyKind = TipStrategyKind::Heuristic;
 /// Jito account to tip to
 pub const TIP_ACCOUNT: Pubkey = pubkey!("ADaUMid9yfUytqMBgopwjb2DTLSokTSzL1zt6iGPaS49");
 /// Ore Mint
 pub const MINT: Pubkey = pubkey!("oreoN2tQbHXVaZsr3pf66A48miqcBXCDJozganhEJgz");
+pub const SOL_MINT: Pubkey = pubkey!("So11111111111111111111111111111111111111112");
 /// Address Lookup Table to use. You will need to set this up first!
 pub const ALT: Pubkey = pubkey!("FQ8LwrRiuhNBfsZpKQdB8eMms59Vyh6VbzSNH7TVVjuW");
 
@@ -31,35 +51,98 @@ pub const ALT: Pubkey = pubkey!("FQ8LwrRiuhNBfsZpKQdB8eMms59Vyh6VbzSNH7TVVjuW");
 pub const CU_LIMIT_MINE: u32 = 2300 + 8200;
 pub const CU_LIMIT_REGISTER: u32 = 7660 + 35_000;
 pub const CU_LIMIT_TRANSFER: u32 = 5000;
+pub const CU_LIMIT_CLAIM: u32 = 40_000;
 
 /// Priority Fee
 pub const PRIO_FEE: u64 = 100_000;
+/// Base fee charged per transaction signature
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5000;
 
 
 /// How long do we look for confirmations for sent transactions. During these congested times
 /// should be > 60
 pub const TIMEOUT: u64 = 120;
 
-/// Deprecated
-pub const MAX_MINERS: u32 = 50;
 /// Number of Miners to Generate. Theoretically up to 255, but above 100 or so you'll need to use
 /// two Address Lookup Tables
 pub const MINER_COUNT: u8 = 100;
+/// Keypairs mined for by this process, each with its own lookup table, miner count and optional
+/// budgets. Transactions never mix authorities. When empty, FUNDING with ALT and MINER_COUNT is used.
+pub const AUTHORITIES: &[Authority] = &[
+    // Authority { keypair: "./second.json", alt: pubkey!("..."), miners: 50, budget_hourly: None, budget_daily: Some(1_000_000_000) },
+];
 /// Number Of Miners Per Transaction. In theory this can go up to 50 or so, but then the
 /// transactions don't get included as often. Seems like 20 works well.
 pub const MINERLIMIT: u32 = 20;
-/// Deprecated?
-pub const RETRIES: u32 = 5;
-/// Deprecated?
-pub const SPAM: usize = 2;
+/// Number of miners claimed for in one transaction
+pub const CLAIM_BATCH: usize = 5;
+
+/// Local file holding miner state and unsubmitted solutions, so they survive restarts
+pub const STATE_FILE: &str = "./ogre-state.json";
+/// Number of landed submissions kept per miner in the state file
+pub const STATE_HISTORY: usize = 100;
+/// Seconds between writes of STATE_FILE while it has unsaved changes. Landed combos and shutdown
+/// write it right away.
+pub const STATE_FLUSH_INTERVAL: u64 = 10;
+/// CSV file every sent and confirmed transaction is appended to
+pub const LEDGER_FILE: &str = "./ogre-ledger.csv";
+/// Address to serve prometheus metrics on at /metrics, e.g. Some("127.0.0.1:9184")
+pub const METRICS_ADDR: Option<&str> = None;
+/// Address to serve the json status and control api on, e.g. Some("127.0.0.1:9185")
+pub const API_ADDR: Option<&str> = None;
+/// Bearer token the api's POST endpoints require. Without one the api only binds to loopback.
+pub const API_TOKEN: Option<&str> = None;
+/// Log file. Rotated once it grows past LOG_MAX_SIZE bytes, keeping LOG_FILES old files
+pub const LOG_FILE: &str = "./ogre.log";
+pub const LOG_MAX_SIZE: u64 = 10 * 1024 * 1024;
+pub const LOG_FILES: u32 = 5;
+/// Log level for ogre itself. Dependencies only log warnings and errors.
+pub const LOG_LEVEL: log::LevelFilter = log::LevelFilter::Info;
+/// Write log lines as json instead of plain text
+pub const LOG_JSON: bool = false;
+/// Seconds between summary log lines when running headless
+pub const SUMMARY_INTERVAL: u64 = 60;
+/// Number of notifications kept for the dashboard and api
+pub const NOTIFICATION_LIMIT: usize = 1000;
+
+/// Alert rules, checked every ALERT_INTERVAL seconds. Empty disables alerting.
+pub const ALERT_RULES: &[AlertRule] = &[
+    AlertRule::LowBalance(100_000_000),
+    AlertRule::NoLanding(15 * 60),
+    AlertRule::FailureRatio(0.5),
+    AlertRule::ThreadStuck(10 * 60),
+];
+/// POST alerts as json to this url, e.g. Some("https://hooks.example.com/ogre")
+pub const ALERT_WEBHOOK: Option<&str> = None;
+/// Run this shell command on alerts, with OGRE_ALERT, OGRE_ALERT_FIRING and OGRE_ALERT_MESSAGE set
+pub const ALERT_COMMAND: Option<&str> = None;
+pub const ALERT_INTERVAL: u64 = 30;
+/// Minimum seconds between repeats of the same firing alert
+pub const ALERT_COOLDOWN: u64 = 60 * 60;
+/// Seconds of history FailureRatio looks at
+pub const ALERT_WINDOW: u64 = 10 * 60;
 
 pub const ORE_DECIMALS: u64 = 1000000000;
-/// Hacky
-pub const SOLPRICE: f64 = 174.0;
+/// Price sources, combined by median. Add a Static source as a last resort fallback.
+pub const PRICE_SOURCES: &[PriceSource] = &[
+    PriceSource::Jupiter,
+    PriceSource::DexScreener,
+    PriceSource::CoinGecko { ore: "ore", sol: "solana" },
+];
+/// What to do when sending a transaction is expected to earn less than PROFIT_MARGIN
+pub const PROFIT_POLICY: ProfitPolicy = ProfitPolicy::Pause;
+/// Required expected profit per sent transaction, in USD
+pub const PROFIT_MARGIN: f64 = 0.0;
+/// Maximum lamports spent on fees, tips and registrations per UTC hour and day. None for no limit.
+pub const BUDGET_HOURLY: Option<u64> = None;
+pub const BUDGET_DAILY: Option<u64> = None;
+/// Seconds without any price update before prices are flagged stale
+pub const PRICE_MAX_AGE: i64 = 10 * 60;
 
 /// Accounts. Don't Change.
 pub const ORE_COLLECTIVE: Pubkey = pubkey!("omcpZynsRS1Py8TP28zeTemamQoRPpuqwdqV8WXnL4M");
 pub const ORE_TREASURY: Pubkey = pubkey!("FTap9fv2GPpWGqrLj3o4c9nHH7p36ih7NbSWHnrkQYqa");
 pub const ORE_TREASURY_TOKENS: Pubkey = pubkey!("37ywg5kxKVb3q3bpvdYhQZBPHrHAXVo91RXoBBj7Boo9");
+#[allow(dead_code)]
 pub const ORE_COLLECTIVE_TREASURY: Pubkey = pubkey!("omc1vcb6CmMywXcDxyL77VaPYU98WyyaP3Mx6LBuaTr");
 pub const ORE_COLLECTIVE_ORE_TREASURY: Pubkey = pubkey!("9idoAEtTrcnoXmrSYMx3pQQYiRLPND3NvcgJnfk6oihW");
@@ -0,0 +1,100 @@
+use std::{env, os::unix::net::UnixDatagram, sync::Arc, time::{Duration, Instant}};
+
+use solana_program::native_token::LAMPORTS_PER_SOL;
+use tokio::signal::unix::{signal, SignalKind};
+
+use crate::{config::{ORE_DECIMALS, SUMMARY_INTERVAL}, ogre::Ogre};
+
+// Exit codes, following sysexits.h so service managers can tell them apart
+pub const EXIT_OK: i32 = 0;
+/// An rpc endpoint could not be reached
+pub const EXIT_UNAVAILABLE: i32 = 69;
+/// A thread panicked
+pub const EXIT_SOFTWARE: i32 = 70;
+/// The configuration is invalid, e.g. the keypair can't be read
+pub const EXIT_CONFIG: i32 = 78;
+
+/// Send a state change to systemd, if running as a notify service
+pub fn sd_notify(state: &str) {
+    let Some(path) = env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    let Ok(socket) = UnixDatagram::unbound() else {
+        return;
+    };
+    let path = path.to_string_lossy().to_string();
+    let result = match path.strip_prefix('@') {
+        Some(name) => {
+            use std::os::linux::net::SocketAddrExt;
+            std::os::unix::net::SocketAddr::from_abstract_name(name)
+                .and_then(|addr| socket.send_to_addr(state.as_bytes(), &addr))
+        }
+        None => socket.send_to(state.as_bytes(), &path),
+    };
+    if let Err(err) = result {
+        log::warn!("sd_notify failed: {}", err);
+    }
+}
+
+/// Exit the whole process when any thread panics, instead of leaving a half dead miner running.
+/// RPC errors are handled where they happen, a panic here is a bug.
+pub fn exit_on_panic() {
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        log::error!("fatal: {}", info);
+        hook(info);
+        std::process::exit(EXIT_SOFTWARE);
+    }));
+}
+
+fn summary(ogre: &Ogre, elapsed: f64) -> String {
+    let stats = ogre.stats.lock().unwrap();
+    format!(
+        "{:.0}s | mined {} submitted {} ({:.4} H/s) | sent {} landed {} failed {} | ore {:.4} | spent {:.6} SOL | tip {} | minable {} loaded {} | wallet {:.4} SOL{}",
+        elapsed,
+        stats.hashes_mined,
+        stats.hashes_submitted,
+        stats.hashes_submitted as f64 / elapsed,
+        stats.sent_sigs,
+        stats.landed_combos,
+        stats.failed_sigs,
+        stats.ore_mined as f64 / ORE_DECIMALS as f64,
+        stats.sol_spent as f64 / LAMPORTS_PER_SOL as f64,
+        stats.tip,
+        ogre.minable.len(),
+        ogre.loaded.len(),
+        stats.balance as f64 / LAMPORTS_PER_SOL as f64,
+        if stats.paused { " | PAUSED" } else if !stats.over_budget.is_empty() { " | OVER BUDGET" } else if stats.unprofitable { " | UNPROFITABLE" } else { "" },
+    )
+}
+
+/// Run without a dashboard: log a summary every SUMMARY_INTERVAL seconds until SIGTERM or SIGINT,
+/// then write the state file. Returns the exit code.
+pub async fn run(ogre: Arc<Ogre>) -> i32 {
+    let start = Instant::now();
+    let (mut term, mut int) = match (signal(SignalKind::terminate()), signal(SignalKind::interrupt())) {
+        (Ok(term), Ok(int)) => (term, int),
+        (Err(err), _) | (_, Err(err)) => {
+            log::error!("failed to install signal handlers: {}", err);
+            return EXIT_SOFTWARE;
+        }
+    };
+    let mut interval = tokio::time::interval(Duration::from_secs(SUMMARY_INTERVAL));
+    sd_notify("READY=1");
+    log::info!("running headless");
+    loop {
+        tokio::select! {
+            _ = term.recv() => break,
+            _ = int.recv() => break,
+            _ = interval.tick() => {
+                let line = summary(&ogre, start.elapsed().as_secs_f64().max(1.0));
+                log::info!("{}", line);
+                sd_notify(&format!("STATUS={}\nWATCHDOG=1", line));
+            }
+        }
+    }
+    sd_notify("STOPPING=1");
+    ogre.store.lock().unwrap().flush(&ogre.stats);
+    log::info!("shutting down: {}", summary(&ogre, start.elapsed().as_secs_f64().max(1.0)));
+    EXIT_OK
+}
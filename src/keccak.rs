@@ -0,0 +1,433 @@
+//! Keccak-256 nonce search over several nonces per permutation.
+//!
+//! A solution hashes the 72 byte message `challenge || miner || nonce`, which fits in one
+//! Keccak block. Each of the 25 state lanes is a [`Lane`] holding that lane for several nonces,
+//! so a single pass of keccak-f[1600] tests all of them: 4 in AVX2 registers, 8 in AVX-512
+//! registers, or one in a plain `u64`. Which width is used is decided at runtime.
+
+// lane indices read closer to the spec than iterators here
+#![allow(clippy::needless_range_loop)]
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+use std::ops::Range;
+
+use oreprog::IndexedSolution;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::keccak::Hash;
+
+const RATE: usize = 136;
+
+const RC: [u64; 24] = [
+    0x0000000000000001, 0x0000000000008082, 0x800000000000808a, 0x8000000080008000,
+    0x000000000000808b, 0x0000000080000001, 0x8000000080008081, 0x8000000000008009,
+    0x000000000000008a, 0x0000000000000088, 0x0000000080008009, 0x000000008000000a,
+    0x000000008000808b, 0x800000000000008b, 0x8000000000008089, 0x8000000000008003,
+    0x8000000000008002, 0x8000000000000080, 0x000000000000800a, 0x800000008000000a,
+    0x8000000080008081, 0x8000000000008080, 0x0000000080000001, 0x8000000080008008,
+];
+
+/// One state lane for `WIDTH` nonces side by side
+trait Lane: Copy {
+    const WIDTH: usize;
+    fn splat(x: u64) -> Self;
+    fn from_fn(f: impl Fn(usize) -> u64) -> Self;
+    fn get(self, l: usize) -> u64;
+    fn xor(self, other: Self) -> Self;
+    /// `!self & other`
+    fn andn(self, other: Self) -> Self;
+    /// Rotate left by `L`, with `R` = `64 - L`
+    fn rotl<const L: i32, const R: i32>(self) -> Self;
+}
+
+impl Lane for u64 {
+    const WIDTH: usize = 1;
+    #[inline(always)]
+    fn splat(x: u64) -> Self { x }
+    #[inline(always)]
+    fn from_fn(f: impl Fn(usize) -> u64) -> Self { f(0) }
+    #[inline(always)]
+    fn get(self, _l: usize) -> u64 { self }
+    #[inline(always)]
+    fn xor(self, other: Self) -> Self { self ^ other }
+    #[inline(always)]
+    fn andn(self, other: Self) -> Self { !self & other }
+    #[inline(always)]
+    fn rotl<const L: i32, const R: i32>(self) -> Self { self.rotate_left(L as u32) }
+}
+
+/// Plain arrays, vectorized by the compiler when built with a wide enough target feature. Used
+/// for AVX-512, whose intrinsics aren't available on our toolchain.
+impl<const N: usize> Lane for [u64; N] {
+    const WIDTH: usize = N;
+    #[inline(always)]
+    fn splat(x: u64) -> Self { [x; N] }
+    #[inline(always)]
+    fn from_fn(f: impl Fn(usize) -> u64) -> Self { std::array::from_fn(f) }
+    #[inline(always)]
+    fn get(self, l: usize) -> u64 { self[l] }
+    #[inline(always)]
+    fn xor(mut self, other: Self) -> Self {
+        for l in 0..N { self[l] ^= other[l] }
+        self
+    }
+    #[inline(always)]
+    fn andn(mut self, other: Self) -> Self {
+        for l in 0..N { self[l] = !self[l] & other[l] }
+        self
+    }
+    #[inline(always)]
+    fn rotl<const L: i32, const R: i32>(mut self) -> Self {
+        for l in 0..N { self[l] = self[l].rotate_left(L as u32) }
+        self
+    }
+}
+
+// SAFETY: the intrinsics are only reached through functions compiled with avx2 enabled, which
+// are only called after detecting avx2
+#[cfg(target_arch = "x86_64")]
+impl Lane for __m256i {
+    const WIDTH: usize = 4;
+    #[inline(always)]
+    fn splat(x: u64) -> Self { unsafe { _mm256_set1_epi64x(x as i64) } }
+    #[inline(always)]
+    fn from_fn(f: impl Fn(usize) -> u64) -> Self { unsafe { _mm256_set_epi64x(f(3) as i64, f(2) as i64, f(1) as i64, f(0) as i64) } }
+    #[inline(always)]
+    fn get(self, l: usize) -> u64 { unsafe { std::mem::transmute::<__m256i, [u64; 4]>(self)[l] } }
+    #[inline(always)]
+    fn xor(self, other: Self) -> Self { unsafe { _mm256_xor_si256(self, other) } }
+    #[inline(always)]
+    fn andn(self, other: Self) -> Self { unsafe { _mm256_andnot_si256(self, other) } }
+    #[inline(always)]
+    fn rotl<const L: i32, const R: i32>(self) -> Self {
+        unsafe { _mm256_or_si256(_mm256_slli_epi64::<L>(self), _mm256_srli_epi64::<R>(self)) }
+    }
+}
+
+/// rho and pi steps, unrolled so every rotation is a constant
+macro_rules! rho_pi {
+    ($a:ident, $(($j:literal, $l:literal)),*) => {
+        let mut last = $a[1];
+        $(
+            let tmp = $a[$j];
+            $a[$j] = last.rotl::<$l, { 64 - $l }>();
+            last = tmp;
+        )*
+        let _ = last;
+    };
+}
+
+/// keccak-f[1600]
+#[inline(always)]
+fn keccak_f<V: Lane>(a: &mut [V; 25]) {
+    for rc in RC {
+        // theta
+        let mut c = [a[0]; 5];
+        for x in 0..5 {
+            c[x] = a[x].xor(a[x + 5]).xor(a[x + 10]).xor(a[x + 15]).xor(a[x + 20]);
+        }
+        for x in 0..5 {
+            let d = c[(x + 4) % 5].xor(c[(x + 1) % 5].rotl::<1, 63>());
+            for y in 0..5 {
+                a[5 * y + x] = a[5 * y + x].xor(d);
+            }
+        }
+        rho_pi!(a, (10, 1), (7, 3), (11, 6), (17, 10), (18, 15), (3, 21), (5, 28), (16, 36), (8, 45), (21, 55), (24, 2), (4, 14),
+            (15, 27), (23, 41), (19, 56), (13, 8), (12, 25), (2, 43), (20, 62), (14, 18), (22, 39), (9, 61), (6, 20), (1, 44));
+        // chi
+        for y in 0..5 {
+            let row = [a[5 * y], a[5 * y + 1], a[5 * y + 2], a[5 * y + 3], a[5 * y + 4]];
+            for x in 0..5 {
+                a[5 * y + x] = row[x].xor(row[(x + 1) % 5].andn(row[(x + 2) % 5]));
+            }
+        }
+        // iota
+        a[0] = a[0].xor(V::splat(rc));
+    }
+}
+
+/// Lane of the message block holding the nonce
+const NONCE_LANE: usize = 8;
+
+/// A miner's padded message block with the nonce lane left empty. Laid out once per miner, so
+/// each attempt only patches in its nonces.
+#[derive(Clone, Copy, Debug)]
+pub struct Midstate([u64; 25]);
+
+impl Midstate {
+    pub fn new(prefix: &[u8; 64]) -> Self {
+        let mut lanes = [0u64; 25];
+        for (lane, bytes) in lanes.iter_mut().zip(prefix.chunks_exact(8)) {
+            *lane = u64::from_le_bytes(bytes.try_into().unwrap());
+        }
+        // keccak padding right after the 72 byte message, and at the end of the block
+        lanes[NONCE_LANE + 1] = 0x01;
+        lanes[RATE / 8 - 1] = 0x80 << 56;
+        Midstate(lanes)
+    }
+
+    /// The `challenge || miner` prefix it was laid out from
+    pub fn prefix(&self) -> [u8; 64] {
+        let mut prefix = [0u8; 64];
+        for (bytes, lane) in prefix.chunks_exact_mut(8).zip(self.0) {
+            bytes.copy_from_slice(&lane.to_le_bytes());
+        }
+        prefix
+    }
+
+    /// Permuted state for the `V::WIDTH` nonces from `start`
+    #[inline(always)]
+    fn permute<V: Lane>(&self, start: u64) -> [V; 25] {
+        let mut state = [V::splat(0); 25];
+        for i in 0..25 {
+            state[i] = V::splat(self.0[i]);
+        }
+        state[NONCE_LANE] = V::from_fn(|l| start.wrapping_add(l as u64));
+        keccak_f(&mut state);
+        state
+    }
+}
+
+/// The hash of nonce `l` in a permuted state
+#[inline(always)]
+fn digest<V: Lane>(state: &[V; 25], l: usize) -> Hash {
+    let mut hash = Hash::default();
+    for i in 0..4 {
+        hash.0[8 * i..8 * i + 8].copy_from_slice(&state[i].get(l).to_le_bytes());
+    }
+    hash
+}
+
+/// Hashes of `prefix || nonce` for the `V::WIDTH` nonces from `start`
+#[inline(always)]
+fn hash_lanes<V: Lane>(prefix: &[u8; 64], start: u64) -> Vec<Hash> {
+    let state = Midstate::new(prefix).permute::<V>(start);
+    (0..V::WIDTH).map(|l| digest(&state, l)).collect()
+}
+
+/// First nonce in `nonces` whose hash is at most `difficulty`. Almost every hash is rejected on
+/// its first 8 bytes, only candidates are assembled and compared in full.
+#[inline(always)]
+fn search_lanes<V: Lane>(midstate: &Midstate, difficulty: &Hash, nonces: Range<u64>) -> Option<(u64, Hash)> {
+    let target = u64::from_be_bytes(difficulty.0[..8].try_into().unwrap());
+    let mut nonce = nonces.start;
+    while nonce < nonces.end {
+        let state = midstate.permute::<V>(nonce);
+        for l in 0..V::WIDTH {
+            // lanes are little endian, hashes compare as big endian bytes
+            let head = state[0].get(l).swap_bytes();
+            if head > target {
+                continue;
+            }
+            let n = nonce.saturating_add(l as u64);
+            if n >= nonces.end {
+                break;
+            }
+            let hash = digest(&state, l);
+            if head < target || hash.le(difficulty) {
+                return Some((n, hash));
+            }
+        }
+        nonce = nonce.saturating_add(V::WIDTH as u64);
+    }
+    None
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn search_avx2(midstate: &Midstate, difficulty: &Hash, nonces: Range<u64>) -> Option<(u64, Hash)> {
+    search_lanes::<__m256i>(midstate, difficulty, nonces)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f")]
+unsafe fn search_avx512(midstate: &Midstate, difficulty: &Hash, nonces: Range<u64>) -> Option<(u64, Hash)> {
+    search_lanes::<[u64; 8]>(midstate, difficulty, nonces)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn hash_avx2(prefix: &[u8; 64], start: u64) -> Vec<Hash> {
+    hash_lanes::<__m256i>(prefix, start)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f")]
+unsafe fn hash_avx512(prefix: &[u8; 64], start: u64) -> Vec<Hash> {
+    hash_lanes::<[u64; 8]>(prefix, start)
+}
+
+/// How many nonces are hashed per permutation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lanes {
+    Scalar,
+    /// 4 lanes in 256 bit registers
+    Avx2,
+    /// 8 lanes in 512 bit registers
+    Avx512,
+}
+
+impl Lanes {
+    /// The widest this cpu supports
+    pub fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx512f") {
+                return Lanes::Avx512;
+            }
+            if is_x86_feature_detected!("avx2") {
+                return Lanes::Avx2;
+            }
+        }
+        Lanes::Scalar
+    }
+
+    /// Whether this cpu can run it
+    pub fn supported(self) -> bool {
+        self.width() <= Lanes::detect().width()
+    }
+
+    pub fn width(self) -> usize {
+        match self {
+            Lanes::Scalar => 1,
+            Lanes::Avx2 => 4,
+            Lanes::Avx512 => 8,
+        }
+    }
+
+    /// First nonce in `nonces` whose hash of `prefix || nonce` is at most `difficulty`
+    pub fn search(self, midstate: &Midstate, difficulty: &Hash, nonces: Range<u64>) -> Option<(u64, Hash)> {
+        match self {
+            // SAFETY: only detected when the cpu has the feature
+            #[cfg(target_arch = "x86_64")]
+            Lanes::Avx2 => unsafe { search_avx2(midstate, difficulty, nonces) },
+            #[cfg(target_arch = "x86_64")]
+            Lanes::Avx512 => unsafe { search_avx512(midstate, difficulty, nonces) },
+            _ => search_lanes::<u64>(midstate, difficulty, nonces),
+        }
+    }
+
+    /// Hashes of `width()` consecutive nonces
+    fn hashes(self, prefix: &[u8; 64], start: u64) -> Vec<Hash> {
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            Lanes::Avx2 => unsafe { hash_avx2(prefix, start) },
+            #[cfg(target_arch = "x86_64")]
+            Lanes::Avx512 => unsafe { hash_avx512(prefix, start) },
+            _ => hash_lanes::<u64>(prefix, start),
+        }
+    }
+
+    /// Compare every lane against the on-chain `IndexedSolution::get_hash`, including a nonce
+    /// range wrapping a 32 bit boundary
+    pub fn self_check(self) -> Result<(), String> {
+        let challenge = solana_sdk::keccak::hash(b"ogre self check");
+        let miner = Pubkey::new_unique();
+        let prefix = prefix(&challenge, &miner);
+        for start in [0, 7, u32::MAX as u64 - 3, u64::MAX - 20] {
+            for (l, hash) in self.hashes(&prefix, start).into_iter().enumerate() {
+                let nonce = start + l as u64;
+                let expected = IndexedSolution { id: 0, bump: 0, nonce }.get_hash(miner, challenge);
+                if hash.0 != expected.0 {
+                    return Err(format!("{:?} lane {} hashes nonce {} to {}, expected {}", self, l, nonce, hash, Hash(expected.0)));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The constant part of a miner's message
+pub fn prefix(challenge: &Hash, miner: &Pubkey) -> [u8; 64] {
+    let mut prefix = [0u8; 64];
+    prefix[..32].copy_from_slice(challenge.as_ref());
+    prefix[32..].copy_from_slice(miner.as_ref());
+    prefix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn supported() -> Vec<Lanes> {
+        [Lanes::Scalar, Lanes::Avx2, Lanes::Avx512].into_iter().filter(|l| l.supported()).collect()
+    }
+
+    fn on_chain(miner: Pubkey, challenge: Hash, nonce: u64) -> Hash {
+        Hash(IndexedSolution { id: 0, bump: 0, nonce }.get_hash(miner, challenge).0)
+    }
+
+    /// One nonce at a time with the on-chain hash
+    fn brute_force(miner: Pubkey, challenge: Hash, difficulty: &Hash, nonces: Range<u64>) -> Option<(u64, Hash)> {
+        nonces.map(|n| (n, on_chain(miner, challenge, n))).find(|(_, hash)| hash.le(difficulty))
+    }
+
+    fn assert_matches(miner: Pubkey, challenge: Hash, difficulty: &Hash, nonces: Range<u64>) {
+        let expected = brute_force(miner, challenge, difficulty, nonces.clone());
+        for lanes in supported() {
+            let found = lanes.search(&Midstate::new(&prefix(&challenge, &miner)), difficulty, nonces.clone());
+            assert_eq!(found, expected, "{:?} searching {:?} against {}", lanes, nonces, difficulty);
+        }
+    }
+
+    #[test]
+    fn search_matches_brute_force_on_unaligned_ranges() {
+        let challenge = solana_sdk::keccak::hash(b"ogre search test");
+        let miner = Pubkey::new_unique();
+        let difficulty = Hash::new_from_array([0x0f; 32]);
+        for nonces in [0..0, 0..1, 0..3, 5..6, 3..17, 1..100, 9..10, 250..1000, u64::MAX - 13..u64::MAX] {
+            assert_matches(miner, challenge, &difficulty, nonces);
+        }
+        // too hard to find anything
+        assert_matches(miner, challenge, &Hash::default(), 0..100);
+    }
+
+    #[test]
+    fn search_compares_in_full_when_the_first_bytes_tie() {
+        let challenge = solana_sdk::keccak::hash(b"ogre tie test");
+        let miner = Pubkey::new_unique();
+        let nonces = 100..164;
+        let (best, hash) = nonces.clone().map(|n| (n, on_chain(miner, challenge, n))).min_by_key(|(_, h)| *h).unwrap();
+
+        // the same first 8 bytes and the rest equal: a solution
+        assert_matches(miner, challenge, &hash, nonces.clone());
+        assert_eq!(brute_force(miner, challenge, &hash, nonces.clone()), Some((best, hash)));
+
+        // the same first 8 bytes and the rest smaller: not a solution
+        let mut below = hash;
+        below.0[8..].fill(0);
+        assert!(below.lt(&hash));
+        assert_matches(miner, challenge, &below, nonces.clone());
+        assert_eq!(brute_force(miner, challenge, &below, nonces), None);
+    }
+
+    #[test]
+    fn search_ignores_lanes_past_the_range_end() {
+        let challenge = solana_sdk::keccak::hash(b"ogre tail test");
+        let miner = Pubkey::new_unique();
+        let (best, hash) = (100..164).map(|n| (n, on_chain(miner, challenge, n))).min_by_key(|(_, h)| *h).unwrap();
+        // the solution is in the same permutation as the range, just past its end
+        for start in best.saturating_sub(7)..best {
+            assert_matches(miner, challenge, &hash, start..best);
+            assert_matches(miner, challenge, &hash, start..best + 1);
+        }
+        let midstate = Midstate::new(&prefix(&challenge, &miner));
+        for lanes in supported() {
+            assert_eq!(lanes.search(&midstate, &hash, best - 1..best), None, "{:?}", lanes);
+            assert_eq!(lanes.search(&midstate, &hash, best - 1..best + 1), Some((best, hash)), "{:?}", lanes);
+        }
+    }
+
+    #[test]
+    fn midstate_keeps_its_prefix() {
+        let prefix = prefix(&solana_sdk::keccak::hash(b"ogre midstate test"), &Pubkey::new_unique());
+        assert_eq!(Midstate::new(&prefix).prefix(), prefix);
+    }
+
+    #[test]
+    fn self_check_passes() {
+        for lanes in supported() {
+            assert_eq!(lanes.self_check(), Ok(()));
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use std::{collections::BTreeMap, error::Error, fs, io::Write, path::Path, sync::Mutex};
+
+use chacha20poly1305::{
+    aead::{Aead, NewAead, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use clap::Subcommand;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use solana_sdk::signature::{read_keypair_file, write_keypair_file, Keypair, Signer};
+use zeroize::Zeroizing;
+
+/// Environment variable holding the keystore password, for running without a terminal
+pub const PASSWORD_ENV: &str = "OGRE_PASSWORD";
+
+const VERSION: u8 = 1;
+/// scrypt cost, 2^17 * 8 * 128 bytes = 128 MiB per unlock
+const LOG_N: u8 = 17;
+const R: u32 = 8;
+const P: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Kdf {
+    log_n: u8,
+    r: u32,
+    p: u32,
+    salt: String,
+}
+
+/// A keypair encrypted with ChaCha20-Poly1305 under a scrypt derived key. Binary fields are
+/// base58, the pubkey is kept in the clear and authenticated with the ciphertext.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Keystore {
+    version: u8,
+    pub pubkey: String,
+    kdf: Kdf,
+    nonce: String,
+    ciphertext: String,
+}
+
+fn derive_key(password: &str, kdf: &Kdf) -> Result<Zeroizing<[u8; 32]>, Box<dyn Error>> {
+    let params = scrypt::Params::new(kdf.log_n, kdf.r, kdf.p, 32).map_err(|err| err.to_string())?;
+    let salt = bs58::decode(&kdf.salt).into_vec()?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    scrypt::scrypt(password.as_bytes(), &salt, &params, key.as_mut()).map_err(|err| err.to_string())?;
+    Ok(key)
+}
+
+impl Keystore {
+    pub fn encrypt(keypair: &Keypair, password: &str) -> Result<Self, Box<dyn Error>> {
+        let mut rng = rand::rng();
+        let kdf = Kdf { log_n: LOG_N, r: R, p: P, salt: bs58::encode(rng.random::<[u8; 32]>()).into_string() };
+        let nonce = rng.random::<[u8; 12]>();
+        let pubkey = keypair.pubkey().to_string();
+        let key = derive_key(password, &kdf)?;
+        let secret = Zeroizing::new(keypair.to_bytes());
+        let ciphertext = ChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: secret.as_ref(), aad: pubkey.as_bytes() })
+            .map_err(|_| "encryption failed")?;
+        Ok(Keystore {
+            version: VERSION,
+            pubkey,
+            kdf,
+            nonce: bs58::encode(nonce).into_string(),
+            ciphertext: bs58::encode(ciphertext).into_string(),
+        })
+    }
+
+    pub fn decrypt(&self, password: &str) -> Result<Keypair, Box<dyn Error>> {
+        if self.version != VERSION {
+            return Err(format!("unsupported keystore version {}", self.version).into());
+        }
+        let key = derive_key(password, &self.kdf)?;
+        let nonce = bs58::decode(&self.nonce).into_vec()?;
+        if nonce.len() != 12 {
+            return Err("invalid keystore nonce".into());
+        }
+        let ciphertext = bs58::decode(&self.ciphertext).into_vec()?;
+        let secret = Zeroizing::new(ChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+            .decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: self.pubkey.as_bytes() })
+            .map_err(|_| "wrong password or corrupted keystore")?);
+        let keypair = Keypair::from_bytes(&secret)?;
+        if keypair.pubkey().to_string() != self.pubkey {
+            return Err("keystore pubkey doesn't match its keypair".into());
+        }
+        Ok(keypair)
+    }
+
+    pub fn read(path: &str) -> Option<Self> {
+        serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+    }
+
+    /// Create the keystore at `path`, readable by the owner only
+    fn write(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(path)?;
+        file.write_all(serde_json::to_string_pretty(self)?.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Password from PASSWORD_ENV, or prompted on the terminal
+fn password(prompt: &str) -> Result<Zeroizing<String>, Box<dyn Error>> {
+    if let Ok(password) = std::env::var(PASSWORD_ENV) {
+        return Ok(Zeroizing::new(password));
+    }
+    Ok(Zeroizing::new(rpassword::prompt_password(prompt)?))
+}
+
+/// Prompt for a new password twice
+fn new_password() -> Result<Zeroizing<String>, Box<dyn Error>> {
+    let password = password("New password: ")?;
+    if std::env::var(PASSWORD_ENV).is_err() && *password != *Zeroizing::new(rpassword::prompt_password("Repeat password: ")?) {
+        return Err("passwords don't match".into());
+    }
+    Ok(password)
+}
+
+/// Decrypted keystores by path, as keypair bytes
+static UNLOCKED: Mutex<BTreeMap<String, Zeroizing<[u8; 64]>>> = Mutex::new(BTreeMap::new());
+
+/// Read a plaintext keypair file or a keystore. A keystore is unlocked on first use, which is
+/// preflight at startup, and stays unlocked in memory for the rest of the process.
+pub fn read_keypair(path: &str) -> Result<Keypair, Box<dyn Error>> {
+    let mut unlocked = UNLOCKED.lock().unwrap();
+    if let Some(bytes) = unlocked.get(path) {
+        return Ok(Keypair::from_bytes(bytes.as_ref())?);
+    }
+    let Some(keystore) = Keystore::read(path) else {
+        return read_keypair_file(path);
+    };
+    let keypair = keystore.decrypt(&password(&format!("Password for {} ({}): ", path, keystore.pubkey))?)?;
+    unlocked.insert(path.to_string(), Zeroizing::new(keypair.to_bytes()));
+    Ok(keypair)
+}
+
+#[derive(Subcommand, Debug)]
+pub enum KeysCommand {
+    /// Generate a new keypair straight into a keystore
+    New {
+        output: String,
+    },
+    /// Encrypt a plaintext keypair file into a keystore
+    Encrypt {
+        input: String,
+        output: String,
+    },
+    /// Decrypt a keystore back into a plaintext keypair file
+    Decrypt {
+        input: String,
+        output: String,
+    },
+}
+
+pub fn run(command: KeysCommand) -> Result<(), Box<dyn Error>> {
+    match command {
+        KeysCommand::New { output } => {
+            let keypair = Keypair::new();
+            Keystore::encrypt(&keypair, &new_password()?)?.write(&output)?;
+            println!("Wrote {} to {}", keypair.pubkey(), output);
+        }
+        KeysCommand::Encrypt { input, output } => {
+            let keypair = read_keypair_file(&input)?;
+            Keystore::encrypt(&keypair, &new_password()?)?.write(&output)?;
+            println!("Wrote {} to {}. Remove the plaintext {} once you've checked it unlocks.", keypair.pubkey(), output, input);
+        }
+        KeysCommand::Decrypt { input, output } => {
+            if Path::new(&output).exists() {
+                return Err(format!("{} already exists", output).into());
+            }
+            let keystore = Keystore::read(&input).ok_or_else(|| format!("{} is not a keystore", input))?;
+            let keypair = keystore.decrypt(&password(&format!("Password for {}: ", input))?)?;
+            write_keypair_file(&keypair, &output)?;
+            println!("Wrote {} to {}", keypair.pubkey(), output);
+        }
+    }
+    Ok(())
+}
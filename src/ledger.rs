@@ -0,0 +1,89 @@
+use std::{fs::{self, File, OpenOptions}, io::{self, BufRead, BufReader, Write}, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::LEDGER_FILE;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Sent,
+    Confirmed,
+    /// landed with an error, only the fee was charged
+    Failed,
+}
+
+/// csv mining record
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Record {
+    pub signature: String,
+    pub status: Status,
+    /// unix timestamp the transaction was sent
+    pub timestamp: u64,
+    /// unix timestamp the transaction was seen confirmed
+    pub confirmed_at: Option<u64>,
+    pub slot: Option<u64>,
+    /// number of miners included
+    pub accounts: u64,
+    /// space separated miner pubkeys
+    pub miners: String,
+    pub bus: u64,
+    /// micro-lamports per CU
+    pub priority_fee: u64,
+    /// lamports
+    pub tip: u64,
+    /// lamports, from the transaction meta, or estimated if it was unavailable
+    pub fee: Option<u64>,
+    /// lamports spent on registering new miners
+    pub registration: Option<u64>,
+    /// ore earned, in base units
+    pub ore: Option<u64>,
+    /// USD price of ore when the record was written
+    #[serde(default)]
+    pub ore_price: Option<f64>,
+    /// USD price of SOL when the record was written
+    #[serde(default)]
+    pub sol_price: Option<f64>,
+}
+
+/// Serializes appends, so a header check and rotation can't interleave with another write
+static APPEND: Mutex<()> = Mutex::new(());
+
+/// Append a record to the ledger file, writing the header if the file is new. A ledger written
+/// with different columns is renamed to `<LEDGER_FILE>.<unix time>` and a new one started, so rows
+/// never end up under a header that doesn't describe them.
+pub fn append(record: &Record) -> Result<(), io::Error> {
+    let _guard = APPEND.lock().unwrap();
+    // serialize into a buffer first, so concurrent writers append whole lines
+    let mut writer = csv::WriterBuilder::new().from_writer(vec![]);
+    writer.serialize(record)?;
+    let data = writer.into_inner().map_err(|e| e.into_error())?;
+    let header_len = data.iter().position(|&b| b == b'\n').map_or(data.len(), |i| i + 1);
+    let (header, row) = data.split_at(header_len);
+
+    let existing = match File::open(LEDGER_FILE) {
+        Ok(file) => {
+            let mut line = String::new();
+            BufReader::new(file).read_line(&mut line)?;
+            Some(line)
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+        Err(err) => return Err(err),
+    };
+    let append_row_only = match existing.as_deref() {
+        None | Some("") => false,
+        Some(line) if line.as_bytes() == header => true,
+        Some(_) => {
+            let rotated = format!("{}.{}", LEDGER_FILE, now());
+            fs::rename(LEDGER_FILE, &rotated)?;
+            log::warn!("{} has different columns, moved it to {}", LEDGER_FILE, rotated);
+            false
+        }
+    };
+    let mut file = OpenOptions::new().create(true).append(true).open(LEDGER_FILE)?;
+    file.write_all(if append_row_only { row } else { &data })
+}
+
+pub fn now() -> u64 {
+    chrono::Utc::now().timestamp() as u64
+}
@@ -0,0 +1,32 @@
+use log::LevelFilter;
+use log4rs::{
+    append::rolling_file::{
+        policy::compound::{roll::fixed_window::FixedWindowRoller, trigger::size::SizeTrigger, CompoundPolicy},
+        RollingFileAppender,
+    },
+    config::{Appender, Config, Logger, Root},
+    encode::{json::JsonEncoder, pattern::PatternEncoder, Encode},
+};
+
+use crate::config::{LOG_FILE, LOG_FILES, LOG_JSON, LOG_LEVEL, LOG_MAX_SIZE};
+
+/// Log to a rotating file. Nothing is logged to the terminal, which belongs to the dashboard.
+pub fn init() -> Result<(), Box<dyn std::error::Error>> {
+    let roller = FixedWindowRoller::builder().build(&format!("{}.{{}}", LOG_FILE), LOG_FILES)?;
+    let policy = CompoundPolicy::new(Box::new(SizeTrigger::new(LOG_MAX_SIZE)), Box::new(roller));
+    let encoder: Box<dyn Encode> = if LOG_JSON {
+        Box::new(JsonEncoder::new())
+    } else {
+        Box::new(PatternEncoder::new("{d(%Y-%m-%d %H:%M:%S%.3f)} {l:<5} {t} - {m}{n}"))
+    };
+    let file = RollingFileAppender::builder()
+        .encoder(encoder)
+        .build(LOG_FILE, Box::new(policy))?;
+
+    let config = Config::builder()
+        .appender(Appender::builder().build("file", Box::new(file)))
+        .logger(Logger::builder().build("ogre", LOG_LEVEL))
+        .build(Root::builder().appender("file").build(LevelFilter::Warn))?;
+    log4rs::init_config(config)?;
+    Ok(())
+}
@@ -1,23 +1,15 @@
-use config::{TIP, MAX_MINERS, MINER_COUNT};
-use glob::glob;
-use solana_program::native_token::LAMPORTS_PER_SOL;
-use solana_sdk::signature::read_keypair_file;
-use stats::ThreadStatus;
-use utils::{get_treasury, get_supply, miner_pubkey, pair_pubkey};
+use config::{TIP, TIP_STRATEGY, INCLUDE_TIP};
+use utils::{get_treasury, get_supply, get_account_balance, miner_pubkey};
 use std::{
-    io::{
-        stdout,
-        Write
-    },
     sync::{Arc, Mutex},
-    time::{ Duration, Instant}, fs::File, task::Wake,
+    time::Duration,
 };
-//use clap::{command, Parser, Subcommand};
+use clap::{Parser, Subcommand};
+use log::Level;
+use solana_client::client_error::ClientError;
+use solana_sdk::keccak::Hash;
 
 
-use comfy_table::{Table, Cell, Color, modifiers::UTF8_SOLID_INNER_BORDERS, presets::UTF8_BORDERS_ONLY, Attribute};
-use colored::Colorize;
-
 #[global_allocator]
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
@@ -28,53 +20,162 @@ mod ogrethread;
 mod submitterv2;
 mod config;
 mod stats;
+mod store;
+mod ledger;
+mod report;
+mod metrics;
+mod api;
+mod claim;
+mod tui;
+mod logging;
+mod daemon;
+mod alerts;
+mod oracle;
+mod profit;
+mod tip;
+mod budget;
+mod authority;
+mod preflight;
+mod keystore;
+mod offline;
+mod keccak;
+mod backend;
+mod bench;
 
 use crate::{
-    miner::Miner, ogre::Ogre, miner::MinerState, config::{RPC, SUBMITTERTHREADS, OGRETHREADS, JITO, ORE_DECIMALS, FUNDING, SOLPRICE}, stats::Stats
+    authority::Authority,
+    miner::Miner, ogre::Ogre, stats::notify, config::{RPC, SUBMITTERTHREADS, OGRETHREADS, STATE_FILE, METRICS_ADDR, API_ADDR, PRIO_FEE, HASH_BACKEND}, stats::Stats, store::Store
 };
-use serde::Deserialize;
 
-#[derive(Deserialize)]
-pub struct DataInnerInner {
-    id: String,
-    mintSymbol: String,
-    vsToken: String,
-    vsTokenSymbol: String,
-    price: f64
+pub async fn loader(authorities: &[Authority], rpc: &str, store: &Mutex<Store>, difficulty: Hash) -> Result<Vec<Miner>, ClientError> {
+    let mut miners: Vec<Miner> = vec![];
+    let mut restored = 0;
+    for authority in authorities {
+        let signer_pk = authority.pubkey();
+        for n in 0..authority.miners {
+            let (miner_key, bump) = miner_pubkey(signer_pk, n);
+            let mut miner = Miner::from_pubkey(signer_pk, &miner_key, n, bump, rpc).await?;
+            let mut store = store.lock().unwrap();
+            if store.restore(&mut miner, difficulty) {
+                restored += 1;
+            }
+            store.record_solution(&miner);
+            miners.push(miner);
+        }
+    }
+    if let Err(err) = store.lock().unwrap().save() {
+        log::error!("failed to save {}: {}", STATE_FILE, err);
+    }
+    log::info!("Loaded {} Miners ({} with stored solutions)", miners.len(), restored);
+    Ok(miners)
 }
 
-#[derive(Deserialize)]
-pub struct DataInner {
-    oreoN2tQbHXVaZsr3pf66A48miqcBXCDJozganhEJgz: DataInnerInner
+#[derive(Parser, Debug)]
+#[command(about = "The Ore Miners Collective Miner")]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-#[derive(Deserialize)]
-pub struct Data {
-    data: DataInner,
-    timeTaken: f64
+#[derive(clap::Args, Debug, Default)]
+struct MineArgs {
+    /// Run without the dashboard, logging periodic summaries instead. For servers and systemd.
+    #[arg(long)]
+    headless: bool,
 }
 
-pub async fn loader(master_key: &str, count: u8, rpc: &str) -> Vec<Miner> {
-    let mut miners: Vec<Miner> = vec![];
-    let signer = read_keypair_file(master_key).unwrap();
-    let signer_pk = pair_pubkey(&signer);
-    for n in 0..count {
-        let (miner_key, bump) = miner_pubkey(signer_pk, n);
-        let miner = Miner::from_pubkey(&miner_key, n, bump, rpc).await;
-        miners.push(miner);
-    }
-    println!("Loaded {} Miners", miners.len());
-    miners
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run the miner (default)
+    Mine(MineArgs),
+    /// Profit and loss report from the ledger
+    Report(report::ReportArgs),
+    /// Create, encrypt and decrypt keypair files
+    #[command(subcommand)]
+    Keys(keystore::KeysCommand),
+    /// Claim the rewards of all miners
+    Claim(offline::BuildArgs),
+    /// Register miners that have no proof account yet
+    Register(offline::BuildArgs),
+    /// Top up miners running low on lamports
+    Fund(offline::FundArgs),
+    /// Add missing miner and proof accounts to the lookup tables
+    Alt(offline::BuildArgs),
+    /// Sign a file written with --build-only
+    Sign(offline::SignArgs),
+    /// Send a signed file and wait for confirmation
+    Broadcast(offline::BroadcastArgs),
+    /// Measure hashes/s of every hashing backend and thread count
+    Bench(bench::BenchArgs),
 }
 
 #[tokio::main]
 async fn main() {
-    let miners = loader(FUNDING, MINER_COUNT, RPC).await;
-    let mut stdout = stdout();
+    let args = Args::parse();
+    match args.command.unwrap_or(Command::Mine(MineArgs::default())) {
+        Command::Mine(args) => {
+            if let Err(err) = logging::init() {
+                eprintln!("failed to set up logging: {}", err);
+            }
+            mine(args).await
+        }
+        Command::Report(args) => {
+            if let Err(err) = report::run(args) {
+                eprintln!("report failed: {}", err);
+                std::process::exit(1);
+            }
+        }
+        Command::Keys(command) => exit_on_error("keys", keystore::run(command)),
+        Command::Claim(args) => exit_on_error("claim", offline::claim(args).await),
+        Command::Register(args) => exit_on_error("register", offline::register(args).await),
+        Command::Fund(args) => exit_on_error("fund", offline::fund(args).await),
+        Command::Alt(args) => exit_on_error("alt", offline::alt(args).await),
+        Command::Sign(args) => exit_on_error("sign", offline::sign(args)),
+        Command::Broadcast(args) => exit_on_error("broadcast", offline::broadcast_file(args).await),
+        Command::Bench(args) => exit_on_error("bench", bench::run(args).await),
+    }
+}
+
+fn exit_on_error(command: &str, result: Result<(), Box<dyn std::error::Error>>) {
+    if let Err(err) = result {
+        eprintln!("{} failed: {}", command, err);
+        std::process::exit(1);
+    }
+}
+
+async fn mine(args: MineArgs) {
+    if args.headless {
+        daemon::exit_on_panic();
+    }
+    if let Err(code) = preflight::run().await {
+        eprintln!("preflight failed, not starting");
+        std::process::exit(code);
+    }
+    let store = match Store::open(STATE_FILE) {
+        Ok(store) => Arc::new(Mutex::new(store)),
+        Err(err) => {
+            log::error!("can't open {}: {}", STATE_FILE, err);
+            eprintln!("can't open {}: {}", STATE_FILE, err);
+            std::process::exit(daemon::EXIT_CONFIG);
+        }
+    };
+    let loaded = match get_treasury(RPC.to_string()).await {
+        Ok(treasury) => loader(authority::all(), RPC, &store, treasury.difficulty.into()).await.map(|miners| (treasury, miners)),
+        Err(err) => Err(err),
+    };
+    let (treasury, miners) = match loaded {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            log::error!("can't load the miners: {}", err);
+            eprintln!("can't load the miners: {}", err);
+            std::process::exit(daemon::EXIT_UNAVAILABLE);
+        }
+    };
 
     let lamports = miners.iter().fold(0 ,|r, s| r + s.lamports);
     let hashes = miners.iter().fold(0 ,|r, s| r + s.total_hashes);
     let rewards = miners.iter().fold(0 ,|r, s| r + s.total_rewards);
+    let budgets = store.lock().unwrap().budgets().clone();
 
     let stats = Arc::new(Mutex::new(Stats { 
         miners: miners.len() as u64, 
@@ -82,24 +183,54 @@ async fn main() {
         hashes_submitted: 0,
         ore_mined: 0,
         sol_spent: 0,
+        costs: Default::default(),
         lamports,
+        balance: 0,
         lifetime_hashes: hashes,
         lifetime_rewards: rewards,
         sent_sigs: 0,
+        failed_sigs: 0,
+        landed_combos: 0,
         threads: vec![],
         price: 0 as f64,
         oldprice: 0 as f64,
+        sol_price: 0 as f64,
+        price_updated: 0,
+        sol_price_updated: 0,
+        price_stale: false,
         supply: 0 as f64,
         oldsupply: 0 as f64,
         rate: 0,
         tip: TIP,
+        tip_strategy: TIP_STRATEGY.strategy(),
+        tip_floor: None,
+        priority_fee: PRIO_FEE,
+        paused: false,
+        unprofitable: false,
+        budget: budgets.global,
+        authority_budgets: budgets.authorities,
+        over_budget: Default::default(),
         oldrate: 0,
-        difficulty: None,
-        notifications: vec![],
+        difficulty: Some(treasury.difficulty),
+        miner_status: Default::default(),
+        notifications: Default::default(),
     }));
+    {
+        let mut stats = stats.lock().unwrap();
+        for miner in &miners {
+            stats.update_miner(miner);
+        }
+    }
 
 
-    let ogre = Arc::new(Ogre::new(OGRETHREADS, SUBMITTERTHREADS, RPC, JITO, miners, stats.clone()).await);
+    let ogre = Arc::new(Ogre::new(OGRETHREADS, SUBMITTERTHREADS, RPC, HASH_BACKEND.backend().into(), miners, stats.clone(), store.clone()).await);
+    if let Some(addr) = METRICS_ADDR {
+        metrics::serve(addr, ogre.clone());
+    }
+    if let Some(addr) = API_ADDR {
+        api::serve(addr, stats.clone());
+    }
+    alerts::spawn(stats.clone());
     {
         // Price Bot
         let stats = stats.clone();
@@ -109,24 +240,40 @@ async fn main() {
                 .build()
                 .unwrap();
             rt.block_on(async {
+                let oracle = oracle::Oracle::new();
+                let client = reqwest::Client::new();
+                let mut payers = authority::all().iter().map(|a| a.fee_payer()).collect::<Vec<_>>();
+                payers.sort();
+                payers.dedup();
                 loop {
-                    let treasury = get_treasury(RPC.to_string()).await;
-                    let res = reqwest::get("https://price.jup.ag/v4/price?ids=oreoN2tQbHXVaZsr3pf66A48miqcBXCDJozganhEJgz").await.unwrap();
-                    let data = res.json::<Data>().await.unwrap();
-
-                    
-
-                    //println!("{}", data.data.oreoN2tQbHXVaZsr3pf66A48miqcBXCDJozganhEJgz.price);
-                    let supply = get_supply(RPC.to_string()).await;
+                    oracle.update(&stats).await;
+                    let tip_floor = if INCLUDE_TIP { tip::get_tip_floor(&client).await } else { None };
+                    let chain = async {
+                        let treasury = get_treasury(RPC.to_string()).await?;
+                        let supply = get_supply(RPC.to_string()).await?;
+                        let mut balance = 0;
+                        for payer in &payers {
+                            balance += get_account_balance(RPC.to_string(), *payer).await?;
+                        }
+                        Ok::<_, ClientError>((treasury, supply, balance))
+                    }.await;
                     {
                         let mut stats = stats.lock().unwrap();
-                        stats.oldprice = stats.price;
-                        stats.price = data.data.oreoN2tQbHXVaZsr3pf66A48miqcBXCDJozganhEJgz.price;
-                        stats.oldsupply = stats.supply;
-                        stats.supply = supply;
-                        stats.oldrate = stats.rate;
-                        stats.rate = treasury.reward_rate;
-                        stats.difficulty = Some(treasury.difficulty);
+                        match chain {
+                            Ok((treasury, supply, balance)) => {
+                                stats.oldsupply = stats.supply;
+                                stats.supply = supply;
+                                stats.oldrate = stats.rate;
+                                stats.rate = treasury.reward_rate;
+                                stats.difficulty = Some(treasury.difficulty);
+                                stats.balance = balance;
+                            }
+                            // keep the last values, the next round tries again
+                            Err(err) => notify!(stats, Level::Warn, "[Stats] can't read the treasury, supply or balances: {}", err),
+                        }
+                        if tip_floor.is_some() {
+                            stats.tip_floor = tip_floor;
+                        }
                     }
                 std::thread::sleep(Duration::from_millis(60 * 1000));
                 }
@@ -134,174 +281,14 @@ async fn main() {
         });
     }
     
-    {
-        let stats = stats.clone();
-
-        std::thread::spawn(move || {
-            let start = Instant::now();
-            let mut last_time = Instant::now();
-
-            let mut table = Table::new();
-            let mut threads = Table::new();
-            let mut ore = Table::new();
-            let mut profits = Table::new();
-
-
-            //let mut log = File::create("stats.txt").unwrap();
-            loop {
-                std::thread::sleep(Duration::from_millis(10));
-
-                table = Table::new();
-                threads = Table::new();
-                ore = Table::new();
-                profits = Table::new();
-
-                table.load_preset(UTF8_BORDERS_ONLY);
-                table.set_content_arrangement(comfy_table::ContentArrangement::DynamicFullWidth);
-                threads.load_preset(UTF8_BORDERS_ONLY);
-                threads.set_content_arrangement(comfy_table::ContentArrangement::DynamicFullWidth);
-                ore.load_preset(UTF8_BORDERS_ONLY);
-                ore.set_content_arrangement(comfy_table::ContentArrangement::DynamicFullWidth);
-
-                profits.load_preset(UTF8_BORDERS_ONLY);
-                profits.set_content_arrangement(comfy_table::ContentArrangement::DynamicFullWidth);
-
-                table.set_header(vec![
-                                 Cell::new("Elapsed").add_attribute(Attribute::Bold), 
-                                 Cell::new("Miners").add_attribute(Attribute::Bold), 
-                                 Cell::new("H sub").add_attribute(Attribute::Bold), 
-                                 Cell::new("H mined").add_attribute(Attribute::Bold), 
-                                 Cell::new("HpS (sub)").add_attribute(Attribute::Bold), 
-                                 Cell::new("HpS (mine)").add_attribute(Attribute::Bold), 
-                                 Cell::new("Ore (session)").add_attribute(Attribute::Bold), 
-                                 Cell::new("Fees (session)").add_attribute(Attribute::Bold), 
-                                 Cell::new("Funds").add_attribute(Attribute::Bold), 
-                                 Cell::new("H (life)").add_attribute(Attribute::Bold),
-                                 Cell::new("Ore (life)").add_attribute(Attribute::Bold), 
-                                 Cell::new("Tx Tries").add_attribute(Attribute::Bold)
-                ]);
-                threads.set_header(vec![
-                                   Cell::new("type").add_attribute(Attribute::Bold), 
-                                   Cell::new("id").add_attribute(Attribute::Bold), 
-                                   Cell::new("status").add_attribute(Attribute::Bold)
-                ]);
-                ore.set_header(vec![
-                               Cell::new("Reward Rate").add_attribute(Attribute::Bold), 
-                               Cell::new("Supply").add_attribute(Attribute::Bold), 
-                               Cell::new("MCAP").add_attribute(Attribute::Bold), 
-                               Cell::new("Price").add_attribute(Attribute::Bold), 
-                               Cell::new("Difficulty").add_attribute(Attribute::Bold), 
-                               Cell::new("Premining Profit").add_attribute(Attribute::Bold),
-                               Cell::new("Tip").add_attribute(Attribute::Bold)
-                ]);
-                profits.set_header(vec![
-                               Cell::new("Profit").add_attribute(Attribute::Bold),
-                               Cell::new("Session").add_attribute(Attribute::Bold),
-                               Cell::new("Life").add_attribute(Attribute::Bold),
-                               Cell::new("USD/Hr").add_attribute(Attribute::Bold),
-                               Cell::new("USD/Day").add_attribute(Attribute::Bold),
-                ]);
-
-                let stats = stats.lock().unwrap();
-                let elapsed = start.elapsed().as_secs_f64();
-                if last_time.elapsed() >= Duration::from_millis(5000) {
-                    let miners = stats.miners;
-                    let hashes_mined = stats.hashes_mined;
-                    let hashes_submitted = stats.hashes_submitted;
-                    let ore_mined = stats.ore_mined;
-                    let sol_spent = stats.sol_spent;
-                    let lamports = stats.lamports;
-                    let l_hashes = stats.lifetime_hashes;
-                    let l_rewards = stats.lifetime_rewards;
-                    let sent_sigs = stats.sent_sigs;
-                    let vthreads = &stats.threads;
-                    let price = stats.price;
-                    let tip = stats.tip;
-                    let oldprice = stats.oldprice;
-                    let supply = stats.supply;
-                    let oldsupply = stats.oldsupply;
-                    let rate = stats.rate;
-                    let oldrate = stats.oldrate;
-                    let difficulty = stats.difficulty.unwrap();
-                    let notifications = &stats.notifications;
-                    stdout.write_all(b"\x1b[2J\x1b[3J\x1b[H").ok();
-                    table.add_row(vec![
-                                  Cell::new(format!("{:.4}s", elapsed)),
-                                  Cell::new(format!("{:.4}", miners)),
-                                  Cell::new(format!("{:.4}", hashes_submitted)),
-                                  Cell::new(format!("{:.4}", hashes_mined)),
-                                  Cell::new(format!("{:.4}", hashes_submitted as f64 / elapsed)),
-                                  Cell::new(format!("{:.4}", hashes_mined as f64 / elapsed)),
-                                  Cell::new(format!("{:.4}", ore_mined as f64 / ORE_DECIMALS as f64)),
-                                  Cell::new(format!("{:.4}", sol_spent as f64 / LAMPORTS_PER_SOL as f64)),
-                                  Cell::new(format!("{:.4}", lamports as f64 / LAMPORTS_PER_SOL as f64)),
-                                  Cell::new(format!("{:.4}", l_hashes)),
-                                  Cell::new(format!("{:.4}", l_rewards as f64 / ORE_DECIMALS as f64)),
-                                  Cell::new(format!("{:.4}", sent_sigs)),
-                    ]);
-                    for thread in vthreads {
-                        threads.add_row(vec![
-                              Cell::new(format!("{:?}", thread.ttype)),
-                              Cell::new(format!("{:?}", thread.id)),
-                              Cell::new(format!("{:?}", thread.activity)),
-                        ]);
-                    }
-
-                    let profit = price * rate as f64 / ORE_DECIMALS as f64 - 0.3;
-                    let a = if profit > 0.0 {
-                        Color::Green
-                    } else {
-                        Color::Red
-                    };
-                    let b = if rate > oldrate {
-                        Color::Green
-                    } else {
-                        Color::Red
-                    };
-                    let c = if price > oldprice {
-                        Color::Green
-                    } else {
-                        Color::Red
-                    };
-                    let d = if supply-oldsupply > 1.0 {
-                        Color::Green
-                    } else {
-                        Color::Red
-                    };
-                    ore.add_row(vec![
-                          Cell::new(format!("{:.9} (${:.4})", rate as f64 / ORE_DECIMALS as f64, (rate as f64/ ORE_DECIMALS as f64) * price)).fg(b),
-                          Cell::new(format!("{:.2} (+{:.2})", supply, supply-oldsupply)).fg(d),
-                          Cell::new(format!("${:.2}", supply * price)).fg(c),
-                          Cell::new(format!("${:.2}", price)).fg(c),
-                          Cell::new(format!("{}", difficulty.to_string())),
-                          Cell::new(format!("{:.4}", profit)).fg(a),
-                          Cell::new(format!("{} (${:.4})", tip, SOLPRICE * tip as f64 / LAMPORTS_PER_SOL as f64)),
-                    ]);
-
-                    profits.add_row(vec![
-                          Cell::new(format!("")),
-                          Cell::new(format!("{:.4} (${:.4})", ore_mined as f64 / ORE_DECIMALS as f64, ore_mined as f64 * price/ ORE_DECIMALS as f64)),
-                          Cell::new(format!("{:.4} (${:.4})", l_rewards as f64 / ORE_DECIMALS as f64,l_rewards as f64 * price / ORE_DECIMALS as f64)),
-                          Cell::new(format!("${:.4}", (ore_mined as f64 * 3600.0  / (elapsed * ORE_DECIMALS as f64)) * price)),
-                          Cell::new(format!("${:.4}", (ore_mined as f64 * 3600.0 * 24.0 / (elapsed * ORE_DECIMALS as f64)) * price)),
-                    ]);
-
-                    println!("{ore}");
-                    println!("{table}");
-                    println!("{profits}");
-                    println!("{threads}");
-                    for notification in notifications {
-                        println!("{}", notification);
-                    }
-
-                    last_time = Instant::now();
-                }
-            }
-        });
+    if args.headless {
+        std::process::exit(daemon::run(ogre).await);
     }
-
-    loop {
-        std::thread::sleep(Duration::from_millis(5000));
+    let result = tui::run(ogre.clone());
+    store.lock().unwrap().flush(&stats);
+    if let Err(err) = result {
+        eprintln!("dashboard failed: {}", err);
+        std::process::exit(daemon::EXIT_SOFTWARE);
     }
-    //println!("End!");
+    std::process::exit(daemon::EXIT_OK);
 }
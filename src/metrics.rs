@@ -0,0 +1,85 @@
+use std::{fmt::Write, sync::Arc, thread};
+
+use log::Level;
+use solana_program::native_token::LAMPORTS_PER_SOL;
+use tiny_http::{Header, Response, Server};
+
+use crate::{config::ORE_DECIMALS, ogre::Ogre, stats::{notify, Stats}};
+
+/// Serve prometheus metrics at http://`addr`/metrics from a background thread
+pub fn serve(addr: &str, ogre: Arc<Ogre>) {
+    let server = match Server::http(addr) {
+        Ok(server) => server,
+        Err(err) => {
+            let mut stats = ogre.stats.lock().unwrap();
+            notify!(stats, Level::Error, "[metrics] failed to bind {}: {:?}", addr, err);
+            return;
+        }
+    };
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = if request.url() == "/metrics" {
+                let body = render(&ogre);
+                let header = Header::from_bytes("Content-Type", "text/plain; version=0.0.4").unwrap();
+                Response::from_string(body).with_header(header)
+            } else {
+                Response::from_string("not found").with_status_code(404)
+            };
+            request.respond(response).ok();
+        }
+    });
+}
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, value: f64) {
+    writeln!(out, "# HELP ogre_{} {}", name, help).ok();
+    writeln!(out, "# TYPE ogre_{} {}", name, kind).ok();
+    writeln!(out, "ogre_{} {}", name, value).ok();
+}
+
+/// Render the current state in the prometheus text format
+pub fn render(ogre: &Ogre) -> String {
+    let mut out = String::new();
+    let stats = ogre.stats.lock().unwrap();
+    render_stats(&mut out, &stats);
+
+    metric(&mut out, "minable_queue", "gauge", "Miners waiting to be mined", ogre.minable.len() as f64);
+    metric(&mut out, "loaded_queue", "gauge", "Miners with a solution waiting to be submitted", ogre.loaded.len() as f64);
+
+    writeln!(out, "# HELP ogre_thread_activity Current activity of each thread").ok();
+    writeln!(out, "# TYPE ogre_thread_activity gauge").ok();
+    for thread in &stats.threads {
+        writeln!(out, "ogre_thread_activity{{id=\"{}\",type=\"{:?}\",activity=\"{}\"}} 1",
+            thread.id, thread.ttype, thread.activity.name()).ok();
+    }
+    writeln!(out, "# HELP ogre_thread_batch Miners held by each submitter thread").ok();
+    writeln!(out, "# TYPE ogre_thread_batch gauge").ok();
+    for thread in &stats.threads {
+        writeln!(out, "ogre_thread_batch{{id=\"{}\",type=\"{:?}\"}} {}",
+            thread.id, thread.ttype, thread.activity.batch()).ok();
+    }
+    out
+}
+
+fn render_stats(out: &mut String, stats: &Stats) {
+    metric(out, "miners", "gauge", "Number of miners", stats.miners as f64);
+    metric(out, "hashes_mined_total", "counter", "Solutions found this session", stats.hashes_mined as f64);
+    metric(out, "hashes_submitted_total", "counter", "Solutions landed this session", stats.hashes_submitted as f64);
+    metric(out, "combos_sent_total", "counter", "Transactions sent", stats.sent_sigs as f64);
+    metric(out, "combos_landed_total", "counter", "Transactions confirmed", stats.landed_combos as f64);
+    metric(out, "combos_failed_total", "counter", "Transactions that failed to send or expired", stats.failed_sigs as f64);
+    metric(out, "ore_mined_total", "counter", "Ore mined this session", stats.ore_mined as f64 / ORE_DECIMALS as f64);
+    metric(out, "sol_spent_total", "counter", "SOL spent this session", stats.sol_spent as f64 / LAMPORTS_PER_SOL as f64);
+    metric(out, "tip_lamports", "gauge", "Current tip", stats.tip as f64);
+    metric(out, "priority_fee_microlamports", "gauge", "Priority fee per compute unit", stats.priority_fee as f64);
+    metric(out, "balance_sol", "gauge", "Funding wallet balance", stats.balance as f64 / LAMPORTS_PER_SOL as f64);
+    metric(out, "ore_price_usd", "gauge", "Ore price", stats.price);
+    metric(out, "sol_price_usd", "gauge", "Sol price", stats.sol_price);
+    metric(out, "price_stale", "gauge", "1 if no price source answered for a while", stats.price_stale as u8 as f64);
+    metric(out, "unprofitable", "gauge", "1 if submission is held back as unprofitable", stats.unprofitable as u8 as f64);
+    metric(out, "over_budget", "gauge", "Authorities idle until a spending budget resets", stats.over_budget.len() as f64);
+    metric(out, "budget_hour_spent_sol", "gauge", "SOL spent this UTC hour", stats.budget.hour_spent as f64 / LAMPORTS_PER_SOL as f64);
+    metric(out, "budget_day_spent_sol", "gauge", "SOL spent this UTC day", stats.budget.day_spent as f64 / LAMPORTS_PER_SOL as f64);
+    metric(out, "reward_rate", "gauge", "Ore reward rate per hash", stats.rate as f64 / ORE_DECIMALS as f64);
+    metric(out, "lifetime_hashes", "gauge", "Lifetime hashes of all miners", stats.lifetime_hashes as f64);
+    metric(out, "lifetime_rewards", "gauge", "Lifetime ore rewards of all miners", stats.lifetime_rewards as f64 / ORE_DECIMALS as f64);
+}
@@ -1,15 +1,11 @@
-use std::{path::Path, thread::panicking, fmt::format, io};
-use oreprog::constants::ORE_PROGRAM_ID;
-use solana_client::rpc_client::RpcClient;
-use solana_sdk::{signature::{Keypair, read_keypair_file, write_keypair_file}, system_transaction, commitment_config::CommitmentConfig};
-use crate::{utils::{proof_pubkey, get_state, get_account_data, get_account_balance, claim_ix}, config::{RPC, JITO, ORE_TREASURY, ORE_COLLECTIVE_ORE_TREASURY, FUNDING_PK, ORE_TREASURY_TOKENS}};
-use sha3::{Digest, Keccak256, digest::generic_array::GenericArray, digest::generic_array::typenum::U32};
+use std::io;
+use solana_client::{client_error::ClientError, rpc_client::RpcClient};
+use solana_sdk::{signature::Keypair, system_transaction, commitment_config::CommitmentConfig};
+use crate::{utils::{proof_pubkey, get_state, get_account_balance}, config::{RPC, JITO, SEARCH_WORKERS}};
+use std::sync::atomic::AtomicBool;
+use crate::{backend::{search_split, HashBackend}, keccak::{self, Midstate}};
 //use solana_client::nonblocking::rpc_client::RpcClient as RPC;
-use solana_sdk::{
-    keccak::{hashv, Hash},
-    signature::Signer
-
-};
+use solana_sdk::keccak::{hashv, Hash};
 use solana_program::pubkey::Pubkey;
 
 type Nonce = u64;
@@ -31,108 +27,94 @@ pub enum MinerState {
 pub struct Miner {
     pub id: u8,
     pub bump: u8,
+    /// The authority owning this miner
+    pub authority: Pubkey,
     pub pubkey: Pubkey,
     pub proof: Pubkey,
     pub state: MinerState,
+    /// The proof hash solutions are currently computed against
+    pub challenge: Hash,
     pub lamports: u64,
     pub total_rewards: u64,
     pub total_hashes: u64,
 }
 
 impl Miner {
-    pub async fn from_pubkey(pubkey: &Pubkey, id: u8, bump: u8, rpc: &str) -> Self {
-        let proof = get_state(rpc.to_string(), pubkey).await;
+    pub async fn from_pubkey(authority: Pubkey, pubkey: &Pubkey, id: u8, bump: u8, rpc: &str) -> Result<Self, ClientError> {
+        let proof = get_state(rpc.to_string(), pubkey).await?;
         let mut total_rewards: u64 = 0;
         let mut total_hashes: u64 = 0;
-        let state = match proof {
+        let (state, challenge) = match proof {
             Some(p) => {
                 total_rewards = p.total_rewards;
                 total_hashes = p.total_hashes;
-                MinerState::Minable(p.hash.into())
+                (MinerState::Minable(p.hash.into()), p.hash.into())
             }, 
             None => {
                 let hash = hashv(&[
                     pubkey.to_bytes().as_slice(),
                 ]);
-                MinerState::New(hash)
+                (MinerState::New(hash), hash)
             }
         };
 
-        let acc = get_account_balance(rpc.to_string(), pubkey.clone()).await;
-        println!("Loaded Miner {} : {} lamports | {} Hashes | {} Ore", pubkey.to_string(), acc, total_hashes, total_rewards);
+        let acc = get_account_balance(rpc.to_string(), *pubkey).await?;
+        log::info!("Loaded Miner {} : {} lamports | {} Hashes | {} Ore", pubkey.to_string(), acc, total_hashes, total_rewards);
         //println!("XYC \"{}\", \"{}\",", pubkey.to_string(), proof_pubkey(pubkey.clone()));
 
-        Miner {
+        Ok(Miner {
             id,
             bump,
-            pubkey: pubkey.clone(),
-            proof: proof_pubkey(pubkey.clone()),
+            authority,
+            pubkey: *pubkey,
+            proof: proof_pubkey(*pubkey),
             state,
+            challenge,
             lamports:acc,
             total_rewards,
             total_hashes
-        }
+        })
     }
 
-    pub fn mine(&mut self, difficulty: Hash) {
-        match self.state {
-            MinerState::Premined(_, _) => {},
-            MinerState::Loaded(_, _) => {},
-            MinerState::New(hash) => {
-                //println!("Mining New Account {}", self.pubkey);
-                let mut next_hash: Hash;
-                let mut nonce: u64 = 0;
-                loop {
-                    next_hash = hashv(&[
-                          hash.to_bytes().as_slice(),
-                          self.pubkey.to_bytes().as_slice(),
-                          nonce.to_le_bytes().as_slice(),
-                    ]);
-
-                    if next_hash.le(&difficulty) {
-                        break;
-                    }
-                    nonce += 1;
-                }
-                self.state = MinerState::Premined(nonce, next_hash);
-                //println!("Found Nonce for Account {}: {}", self.pubkey, nonce);
-            },
-            MinerState::Minable(hash) => {
-                //println!("Mining Minable Account {}", self.pubkey);
-                let mut next_hash: Hash;
-                let mut next_hash_gen: [u8; 32];
-                let mut nonce: u64 = 0;
-                let hash_bytes = hash.clone().to_bytes();
-                let key_bytes = self.pubkey.to_bytes();
-                let combined = [&hash_bytes[..], &key_bytes].concat();
-
-                let mut hasher = Keccak256::default();
-                loop {
-                    // hasher.update(hash_bytes.as_slice());
-                    // hasher.update(key_bytes.as_slice());
-                    hasher.update(combined.as_slice());
-                    hasher.update(nonce.to_le_bytes().as_slice());
-
-                    //hasher.finalize_into_reset(&mut next_hash_gen);
-                    next_hash = Hash(<[u8; 32]>::try_from(hasher.finalize_reset().as_slice()).unwrap());
-
-                    // next_hash = hashv(&[
-                    //       hash_bytes.as_slice(),
-                    //       key_bytes.as_slice(),
-                    //       nonce.to_le_bytes().as_slice(),
-                    // ]);
-
-                    if next_hash.le(&difficulty) {
-                        break;
-                    }
-                    nonce += 1;
-                    //hasher.reset();
-                }
-                //println!("{:?}", next_hash);
-                self.state = MinerState::Loaded(nonce, next_hash);
-                //println!("Found Nonce for Account {}: {}", self.pubkey, nonce);
-            },
+    /// Find a nonce for the current challenge at `difficulty`. Returns false, leaving the miner
+    /// as it was, if `cancel` was raised first.
+    pub fn mine(&mut self, difficulty: Hash, backend: &dyn HashBackend, cancel: &AtomicBool) -> bool {
+        let hash = match self.state {
+            MinerState::Premined(_, _) | MinerState::Loaded(_, _) => return true,
+            MinerState::New(hash) | MinerState::Minable(hash) => hash,
+        };
+        let midstate = Midstate::new(&keccak::prefix(&hash, &self.pubkey));
+        let Some((nonce, next_hash)) = search_split(backend, &midstate, &difficulty, SEARCH_WORKERS, cancel) else {
+            return false;
+        };
+        self.state = match self.state {
+            MinerState::New(_) => MinerState::Premined(nonce, next_hash),
+            _ => MinerState::Loaded(nonce, next_hash),
+        };
+        true
+    }
+
+    /// Check a held solution against `difficulty`, rehashing its nonce with the challenge. An
+    /// invalid solution is dropped, putting the miner back to New or Minable.
+    /// Returns whether the miner still holds a valid solution.
+    pub fn revalidate(&mut self, difficulty: &Hash) -> bool {
+        let (nonce, hash) = match self.state {
+            MinerState::Premined(nonce, hash) | MinerState::Loaded(nonce, hash) => (nonce, hash),
+            MinerState::New(_) | MinerState::Minable(_) => return false,
+        };
+        let rehash = hashv(&[
+            self.challenge.as_ref(),
+            self.pubkey.as_ref(),
+            nonce.to_le_bytes().as_slice(),
+        ]);
+        if rehash == hash && hash.le(difficulty) {
+            return true;
         }
+        self.state = match self.state {
+            MinerState::Premined(_, _) => MinerState::New(self.challenge),
+            _ => MinerState::Minable(self.challenge),
+        };
+        false
     }
 
     /// send lamports to this account, if it has fewer than 1/2 of given amount
@@ -145,15 +127,15 @@ impl Miner {
         let client = RpcClient::new_with_commitment(RPC, CommitmentConfig::confirmed());
         let jito = RpcClient::new_with_commitment(JITO, CommitmentConfig::confirmed());
 
-        let (hash, slot) = client.get_latest_blockhash_with_commitment(CommitmentConfig::confirmed()).unwrap();
+        let (hash, _slot) = client.get_latest_blockhash_with_commitment(CommitmentConfig::confirmed()).unwrap();
         let tx = system_transaction::transfer(&payer, &self.pubkey, diff, hash);
         let a = jito.send_transaction(&tx).unwrap();
-        println!("Funding TX for {} {:?}", self.pubkey.to_string(), a);
+        println!("Funding TX for {} {:?}", self.pubkey, a);
         Ok(())
     }
 
     /// withdraw all ore from this account
-    pub fn withdraw_ore(&mut self, receiver: Pubkey) -> Result<(), io::Error>{
+    pub fn withdraw_ore(&mut self, _receiver: Pubkey) -> Result<(), io::Error>{
         // let ix = claim_ix(
         //     oreprog::accounts::Claim{
         //         authority: FUNDING_PK,
@@ -178,13 +160,13 @@ impl Miner {
     }
 
     /// withdraw all lamports from this account
-    pub fn withdraw_lamports(&mut self, receiver: Pubkey) -> Result<(), io::Error>{
+    pub fn withdraw_lamports(&mut self, _receiver: Pubkey) -> Result<(), io::Error>{
         // TODO:
         Ok(())
     }
 
     /// withdraw all ore and lamports from this account
-    pub fn empty(&mut self, receiver: Pubkey) -> Result<(), io::Error>{
+    pub fn empty(&mut self, _receiver: Pubkey) -> Result<(), io::Error>{
         // TODO:
         Ok(())
     }
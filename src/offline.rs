@@ -0,0 +1,381 @@
+use std::{collections::HashSet, error::Error, fs, time::Duration};
+
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use solana_client::{nonblocking::rpc_client::RpcClient, nonce_utils::nonblocking as nonce_utils, rpc_config::RpcSendTransactionConfig};
+use solana_program::{
+    address_lookup_table::{instruction::extend_lookup_table, state::AddressLookupTable},
+    instruction::Instruction,
+    pubkey::Pubkey,
+    system_instruction,
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    compute_budget::ComputeBudgetInstruction,
+    signature::{Keypair, Signature, Signer},
+    transaction::VersionedTransaction,
+};
+
+use crate::{
+    authority::{self, Authority},
+    claim::claim_instructions,
+    config::{ALT, CU_LIMIT_REGISTER, CU_LIMIT_TRANSFER, MINERLIMIT, MINER_COUNT, PRIO_FEE, RPC, TIMEOUT},
+    keystore::read_keypair,
+    utils::{compile_message, get_account_balance, get_state, miner_pubkey, proof_pubkey, registration_ix, signers},
+};
+
+/// Addresses added to a lookup table per transaction
+const ALT_BATCH: usize = 20;
+
+#[derive(Args, Debug)]
+pub struct BuildArgs {
+    /// Write unsigned transactions to FILE for `ogre sign` instead of sending them
+    #[arg(long, value_name = "FILE")]
+    pub build_only: Option<String>,
+    /// Build for this authority, whose keypair isn't on this machine, instead of AUTHORITIES
+    #[arg(long, requires = "build_only")]
+    pub authority: Option<Pubkey>,
+    /// Fee payer whose keypair isn't on this machine. Defaults to FEE_PAYER, or --authority
+    #[arg(long, requires = "build_only")]
+    pub payer: Option<Pubkey>,
+    /// Lookup table of --authority
+    #[arg(long, default_value_t = ALT)]
+    pub alt: Pubkey,
+    /// Number of miners of --authority
+    #[arg(long, default_value_t = MINER_COUNT)]
+    pub miners: u8,
+    /// Durable nonce account with the fee payer as nonce authority, one per transaction.
+    /// Without, transactions expire about a minute after building.
+    #[arg(long = "nonce", value_name = "PUBKEY")]
+    pub nonces: Vec<Pubkey>,
+}
+
+#[derive(Args, Debug)]
+pub struct FundArgs {
+    #[command(flatten)]
+    pub build: BuildArgs,
+    /// Top up miners holding less than half of this many lamports to it
+    #[arg(long)]
+    pub lamports: u64,
+}
+
+#[derive(Args, Debug)]
+pub struct SignArgs {
+    /// File written by --build-only, signed in place
+    pub file: String,
+    /// Keypair or keystore to sign with, can be repeated
+    #[arg(long = "keypair", required = true)]
+    pub keypairs: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct BroadcastArgs {
+    /// Signed file to send
+    pub file: String,
+}
+
+/// One transaction in a --build-only file
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+    description: String,
+    /// bincode serialized VersionedTransaction, base58
+    transaction: String,
+    /// signature, once `ogre broadcast` saw it confirm
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    confirmed: Option<String>,
+}
+
+impl Entry {
+    fn new(description: String, tx: &VersionedTransaction) -> Result<Self, Box<dyn Error>> {
+        Ok(Entry { description, transaction: bs58::encode(bincode::serialize(tx)?).into_string(), confirmed: None })
+    }
+
+    fn transaction(&self) -> Result<VersionedTransaction, Box<dyn Error>> {
+        Ok(bincode::deserialize(&bs58::decode(&self.transaction).into_vec()?)?)
+    }
+}
+
+fn read_entries(path: &str) -> Result<Vec<Entry>, Box<dyn Error>> {
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+fn write_entries(path: &str, entries: &[Entry]) -> Result<(), Box<dyn Error>> {
+    Ok(fs::write(path, serde_json::to_string_pretty(entries)?)?)
+}
+
+/// Signers a transaction still lacks
+fn missing_signers(tx: &VersionedTransaction) -> Vec<Pubkey> {
+    let required = tx.message.header().num_required_signatures as usize;
+    tx.message.static_account_keys()[..required].iter()
+        .zip(&tx.signatures)
+        .filter(|(_, sig)| **sig == Signature::default())
+        .map(|(key, _)| *key)
+        .collect()
+}
+
+/// An authority to build transactions for
+struct Target {
+    authority: Pubkey,
+    payer: Pubkey,
+    alt: Pubkey,
+    miners: u8,
+    /// the configured authority, if its keypairs are on this machine
+    config: Option<&'static Authority>,
+}
+
+fn targets(args: &BuildArgs) -> Vec<Target> {
+    match args.authority {
+        Some(authority) => vec![Target {
+            authority,
+            payer: args.payer.unwrap_or(authority),
+            alt: args.alt,
+            miners: args.miners,
+            config: None,
+        }],
+        None => authority::all().iter().map(|a| Target {
+            authority: a.pubkey(),
+            payer: args.payer.unwrap_or_else(|| a.fee_payer()),
+            alt: a.alt,
+            miners: a.miners,
+            config: Some(a),
+        }).collect(),
+    }
+}
+
+/// Instructions of one transaction to build
+struct Unsigned<'a> {
+    description: String,
+    target: &'a Target,
+    /// lookup table to compile against
+    alt: Option<Pubkey>,
+    ixs: Vec<Instruction>,
+}
+
+/// Compile the transactions, then write them unsigned for --build-only or sign and send them here
+async fn finish(args: &BuildArgs, unsigned: Vec<Unsigned<'_>>) -> Result<(), Box<dyn Error>> {
+    if unsigned.is_empty() {
+        println!("Nothing to do");
+        return Ok(());
+    }
+    if !args.nonces.is_empty() && args.nonces.len() < unsigned.len() {
+        return Err(format!("{} transactions but only {} nonce accounts", unsigned.len(), args.nonces.len()).into());
+    }
+    let client = RpcClient::new_with_commitment(RPC.to_string(), CommitmentConfig::confirmed());
+    let mut built = vec![];
+    for (i, mut tx) in unsigned.into_iter().enumerate() {
+        let blockhash = match args.nonces.get(i) {
+            Some(nonce) => {
+                tx.ixs.insert(0, system_instruction::advance_nonce_account(nonce, &tx.target.payer));
+                let account = nonce_utils::get_account_with_commitment(&client, nonce, CommitmentConfig::confirmed()).await?;
+                nonce_utils::data_from_account(&account)?.blockhash()
+            }
+            None => client.get_latest_blockhash().await?,
+        };
+        let message = compile_message(&client, &tx.ixs, tx.alt, &tx.target.payer, blockhash).await?;
+        let signatures = vec![Signature::default(); message.header().num_required_signatures as usize];
+        built.push((tx.description, tx.target, VersionedTransaction { signatures, message }));
+    }
+
+    if let Some(path) = &args.build_only {
+        let mut entries = vec![];
+        for (description, _, tx) in &built {
+            let signers = missing_signers(tx).iter().map(|k| k.to_string()).collect::<Vec<_>>();
+            println!("{} (needs {})", description, signers.join(", "));
+            entries.push(Entry::new(description.clone(), tx)?);
+        }
+        write_entries(path, &entries)?;
+        println!("Wrote {} unsigned transactions to {}", entries.len(), path);
+        return Ok(());
+    }
+
+    let mut signed = vec![];
+    for (description, target, mut tx) in built {
+        let config = target.config.ok_or("no local keypair for this authority")?;
+        let keypair = config.read_keypair();
+        let payer = config.read_fee_payer();
+        sign_transaction(&mut tx, &signers(&keypair, &payer));
+        signed.push((description, tx));
+    }
+    broadcast(&client, signed).await
+}
+
+/// Add signatures of the given keypairs where the transaction requires them
+fn sign_transaction(tx: &mut VersionedTransaction, keypairs: &[&Keypair]) -> usize {
+    let message = tx.message.serialize();
+    let required = tx.message.header().num_required_signatures as usize;
+    let mut added = 0;
+    for keypair in keypairs {
+        if let Some(i) = tx.message.static_account_keys()[..required].iter().position(|k| *k == keypair.pubkey()) {
+            tx.signatures[i] = keypair.sign_message(&message);
+            added += 1;
+        }
+    }
+    added
+}
+
+/// Send a fully signed transaction and wait for it to confirm
+async fn send_and_confirm(client: &RpcClient, description: &str, tx: &VersionedTransaction) -> Result<Signature, Box<dyn Error>> {
+    if !tx.verify_with_results().into_iter().all(|ok| ok) {
+        return Err(format!("{}: missing or invalid signatures", description).into());
+    }
+    let sig = client.send_transaction_with_config(tx, RpcSendTransactionConfig::default()).await?;
+    println!("{}: sent {}", description, sig);
+    for _ in 0..TIMEOUT {
+        match client.get_signature_status(&sig).await? {
+            Some(Ok(())) => {
+                println!("{}: confirmed", description);
+                return Ok(sig);
+            }
+            Some(Err(err)) => return Err(format!("{}: {} failed: {}", description, sig, err).into()),
+            None => tokio::time::sleep(Duration::from_secs(1)).await,
+        }
+    }
+    Err(format!("{}: {} not confirmed after {}s", description, sig, TIMEOUT).into())
+}
+
+/// Send fully signed transactions one by one, each once the previous one confirmed
+async fn broadcast(client: &RpcClient, txs: Vec<(String, VersionedTransaction)>) -> Result<(), Box<dyn Error>> {
+    for (description, tx) in txs {
+        send_and_confirm(client, &description, &tx).await?;
+    }
+    Ok(())
+}
+
+fn with_budget(cu: u32, mut ixs: Vec<Instruction>) -> Vec<Instruction> {
+    ixs.insert(0, ComputeBudgetInstruction::set_compute_unit_limit(cu));
+    ixs.insert(1, ComputeBudgetInstruction::set_compute_unit_price(PRIO_FEE));
+    ixs
+}
+
+/// `ogre claim`: claim the rewards of every miner
+pub async fn claim(args: BuildArgs) -> Result<(), Box<dyn Error>> {
+    let targets = targets(&args);
+    let mut unsigned = vec![];
+    for target in &targets {
+        for ixs in claim_instructions(RPC, target.authority, target.miners, target.payer, PRIO_FEE).await? {
+            unsigned.push(Unsigned {
+                description: format!("claim {} miners of {}", ixs.len() - 3, target.authority),
+                target,
+                alt: Some(target.alt),
+                ixs,
+            });
+        }
+    }
+    finish(&args, unsigned).await
+}
+
+/// `ogre register`: register every miner without a proof account yet
+pub async fn register(args: BuildArgs) -> Result<(), Box<dyn Error>> {
+    let targets = targets(&args);
+    let mut unsigned = vec![];
+    for target in &targets {
+        let mut ixs = vec![];
+        for id in 0..target.miners {
+            let (miner, _) = miner_pubkey(target.authority, id);
+            if get_state(RPC.to_string(), &miner).await?.is_none() {
+                ixs.push(registration_ix(target.authority, target.payer, miner, id));
+            }
+        }
+        for chunk in ixs.chunks(MINERLIMIT as usize) {
+            unsigned.push(Unsigned {
+                description: format!("register {} miners of {}", chunk.len(), target.authority),
+                target,
+                alt: Some(target.alt),
+                ixs: with_budget(CU_LIMIT_REGISTER * chunk.len() as u32, chunk.to_vec()),
+            });
+        }
+    }
+    finish(&args, unsigned).await
+}
+
+/// `ogre fund`: top up miners running low on lamports from the fee payer
+pub async fn fund(args: FundArgs) -> Result<(), Box<dyn Error>> {
+    let targets = targets(&args.build);
+    let mut unsigned = vec![];
+    for target in &targets {
+        let mut ixs = vec![];
+        for id in 0..target.miners {
+            let (miner, _) = miner_pubkey(target.authority, id);
+            let balance = get_account_balance(RPC.to_string(), miner).await?;
+            if balance < args.lamports / 2 {
+                ixs.push(system_instruction::transfer(&target.payer, &miner, args.lamports - balance));
+            }
+        }
+        for chunk in ixs.chunks(MINERLIMIT as usize) {
+            unsigned.push(Unsigned {
+                description: format!("fund {} miners of {}", chunk.len(), target.authority),
+                target,
+                alt: Some(target.alt),
+                ixs: with_budget(CU_LIMIT_TRANSFER * chunk.len() as u32, chunk.to_vec()),
+            });
+        }
+    }
+    finish(&args.build, unsigned).await
+}
+
+/// `ogre alt`: add missing miner and proof accounts to each authority's lookup table. The table
+/// has to exist already, with the authority as its authority.
+pub async fn alt(args: BuildArgs) -> Result<(), Box<dyn Error>> {
+    let client = RpcClient::new_with_commitment(RPC.to_string(), CommitmentConfig::confirmed());
+    let targets = targets(&args);
+    let mut unsigned = vec![];
+    for target in &targets {
+        let account = client.get_account(&target.alt).await?;
+        let table = AddressLookupTable::deserialize(&account.data)?;
+        let present = table.addresses.iter().collect::<HashSet<_>>();
+        let missing = (0..target.miners)
+            .flat_map(|id| {
+                let (miner, _) = miner_pubkey(target.authority, id);
+                [miner, proof_pubkey(miner)]
+            })
+            .filter(|key| !present.contains(key))
+            .collect::<Vec<_>>();
+        for chunk in missing.chunks(ALT_BATCH) {
+            unsigned.push(Unsigned {
+                description: format!("add {} accounts of {} to {}", chunk.len(), target.authority, target.alt),
+                target,
+                alt: None,
+                ixs: vec![extend_lookup_table(target.alt, target.authority, Some(target.payer), chunk.to_vec())],
+            });
+        }
+    }
+    finish(&args, unsigned).await
+}
+
+/// `ogre sign`: sign a --build-only file in place with local keypairs
+pub fn sign(args: SignArgs) -> Result<(), Box<dyn Error>> {
+    let keypairs = args.keypairs.iter().map(|path| read_keypair(path)).collect::<Result<Vec<_>, _>>()?;
+    let keypairs = keypairs.iter().collect::<Vec<_>>();
+    let mut entries = read_entries(&args.file)?;
+    for entry in &mut entries {
+        let mut tx = entry.transaction()?;
+        let added = sign_transaction(&mut tx, &keypairs);
+        let missing = missing_signers(&tx);
+        if missing.is_empty() {
+            println!("{}: {} signatures added, complete", entry.description, added);
+        } else {
+            let missing = missing.iter().map(|k| k.to_string()).collect::<Vec<_>>();
+            println!("{}: {} signatures added, still needs {}", entry.description, added, missing.join(", "));
+        }
+        *entry = Entry::new(entry.description.clone(), &tx)?;
+    }
+    write_entries(&args.file, &entries)
+}
+
+/// `ogre broadcast`: send a signed file. Every confirmed transaction is marked in the file right
+/// away, so running it again after a failure resumes with the first one that didn't confirm.
+pub async fn broadcast_file(args: BroadcastArgs) -> Result<(), Box<dyn Error>> {
+    let client = RpcClient::new_with_commitment(RPC.to_string(), CommitmentConfig::confirmed());
+    let mut entries = read_entries(&args.file)?;
+    for i in 0..entries.len() {
+        if let Some(sig) = &entries[i].confirmed {
+            println!("{}: already confirmed {}", entries[i].description, sig);
+            continue;
+        }
+        let tx = entries[i].transaction()?;
+        let sig = send_and_confirm(&client, &entries[i].description, &tx).await?;
+        entries[i].confirmed = Some(sig.to_string());
+        write_entries(&args.file, &entries)?;
+    }
+    Ok(())
+}
@@ -1,23 +1,26 @@
-use crossbeam_channel::{unbounded, Sender, Receiver};
-use solana_client::nonblocking::rpc_client::RpcClient as RPC;
+use crossbeam_channel::{unbounded, Receiver};
 use std::{thread, sync::{Arc, Mutex}};
 
 use crate::{
+    backend::HashBackend,
     ogrethread::OgreThread,
     submitterv2::SubmitterThread,
     miner::Miner,
     miner::MinerState,
-    stats::Stats, config::{PRIO_FEE, RETRIES, MINERLIMIT},
+    stats::Stats, store::{self, Store}, config::MINERLIMIT,
 };
 
 pub struct Ogre {
-    pub rpc: String,
-    pub jito: String,
     pub stats: Arc<Mutex<Stats>>,
+    pub store: Arc<Mutex<Store>>,
+    /// miners waiting to be mined
+    pub minable: Receiver<Miner>,
+    /// miners waiting to be submitted
+    pub loaded: Receiver<Miner>,
 }
 
 impl Ogre {
-    pub async fn new(ogrethreads: u32, submitterthreads: u32, rpc: &str, jito: &str, miners: Vec<Miner>, stats: Arc<Mutex<Stats>>) -> Self {
+    pub async fn new(ogrethreads: u32, submitterthreads: u32, rpc: &str, backend: Arc<dyn HashBackend>, miners: Vec<Miner>, stats: Arc<Mutex<Stats>>, store: Arc<Mutex<Store>>) -> Self {
         if submitterthreads < 1 {
             panic!()
         }
@@ -43,7 +46,7 @@ impl Ogre {
             let sender = minable_sender.clone();
             // submitter receives from loaded
             let receiver = loaded_receiver.clone();
-            let mut submitter = SubmitterThread::new(i.try_into().unwrap(), MINERLIMIT as usize, RETRIES, PRIO_FEE, sender, receiver, rpc.to_string().clone(), stats.clone());
+            let mut submitter = SubmitterThread::new(i.try_into().unwrap(), MINERLIMIT as usize, sender, receiver, rpc.to_string().clone(), stats.clone(), store.clone());
             thread::spawn(move || {
                 let rt = tokio::runtime::Builder::new_multi_thread()
                     .enable_all()
@@ -60,14 +63,17 @@ impl Ogre {
             let sender = loaded_sender.clone();
             // ogre receives from minable
             let receiver = minable_receiver.clone();
-            let mut ogre = OgreThread::new((submitterthreads + i).try_into().unwrap(), receiver, sender, rpc.to_string(), stats.clone()).await;
+            // and moves solutions the difficulty invalidates back from loaded to minable
+            let mut ogre = OgreThread::new((submitterthreads + i).try_into().unwrap(), receiver, sender, minable_sender.clone(), loaded_receiver.clone(), backend.clone(), stats.clone(), store.clone()).await;
             thread::spawn(move || ogre.start());
         }
+        store::spawn_flusher(store.clone(), stats.clone());
 
         Self {
-            rpc: rpc.to_string(),
-            jito: jito.to_string(),
-            stats
+            stats,
+            store,
+            minable: minable_receiver,
+            loaded: loaded_receiver,
         }
 
     }
@@ -1,4 +1,4 @@
-use std::{time::Duration, path::Path, sync::{Arc, Mutex}};
+use std::{time::Duration, sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}}};
 
 use crossbeam_channel::{TryRecvError, Sender, Receiver};
 use solana_sdk::{
@@ -6,9 +6,17 @@ use solana_sdk::{
 };
 
 use crate::{
-    miner::Miner, utils::get_treasury, stats::{Stats, ThreadStatus, ThreadType, Activity},
+    backend::HashBackend, miner::Miner, stats::{Stats, ThreadStatus, ThreadType, Activity}, store::Store,
 };
 
+/// How often a running search looks for a new difficulty
+const DIFFICULTY_POLL: Duration = Duration::from_secs(1);
+
+/// The difficulty last read by the price loop, if it has run yet
+pub fn latest_difficulty(stats: &Mutex<Stats>) -> Option<Hash> {
+    stats.lock().unwrap().difficulty.map(Into::into)
+}
+
 /// OgreThread just mines the next hash offline. No need for rpc connection or anything.
 pub struct OgreThread {
     pub id: usize,
@@ -16,12 +24,18 @@ pub struct OgreThread {
     pub mq: Receiver<Miner>,
     /// the queue to which to push miners
     pub lq: Sender<Miner>,
+    /// the minable queue, for miners whose solution no longer meets the difficulty
+    pub mq_sender: Sender<Miner>,
+    /// the loaded queue, to revalidate its solutions when the difficulty changes
+    pub lq_receiver: Receiver<Miner>,
     pub difficulty: Hash,
+    pub backend: Arc<dyn HashBackend>,
     pub stats: Arc<Mutex<Stats>>,
+    pub store: Arc<Mutex<Store>>,
 }
 impl OgreThread {
-    pub async fn new(id: usize, mq: Receiver<Miner>, lq: Sender<Miner>, rpc: String, stats: Arc<Mutex<Stats>>) -> Self {
-        let treasury = get_treasury(rpc).await;
+    #[allow(clippy::too_many_arguments)]
+    pub async fn new(id: usize, mq: Receiver<Miner>, lq: Sender<Miner>, mq_sender: Sender<Miner>, lq_receiver: Receiver<Miner>, backend: Arc<dyn HashBackend>, stats: Arc<Mutex<Stats>>, store: Arc<Mutex<Store>>) -> Self {
         {
             let mut stats = stats.lock().unwrap();
             stats.threads.push(
@@ -35,13 +49,19 @@ impl OgreThread {
             id,
             mq,
             lq,
-            difficulty: treasury.difficulty.into(),
-            stats
+            mq_sender,
+            lq_receiver,
+            // main reads it before starting any thread
+            difficulty: latest_difficulty(&stats).unwrap_or_default(),
+            backend,
+            stats,
+            store
         }
     }
 
     pub fn start(&mut self) {
         loop {
+            self.follow_difficulty();
             let message = self.mq.try_recv();
             match message {
                 Ok(mut miner) => {
@@ -49,13 +69,16 @@ impl OgreThread {
                         let mut stats = self.stats.lock().unwrap();
                         stats.threads[self.id].activity = Activity::Mining;
                     }
-                    miner.mine(self.difficulty);
-                    self.lq.send(miner).unwrap();
+                    self.mine(&mut miner);
+                    log::debug!("thread {} found solution for miner {} {:?}", self.id, miner.pubkey, miner.state);
+                    self.store.lock().unwrap().record_solution(&miner);
                     {
                         let mut stats = self.stats.lock().unwrap();
                         stats.threads[self.id].activity = Activity::Idle;
                         stats.hashes_mined += 1;
+                        stats.update_miner(&miner);
                     }
+                    self.lq.send(miner).unwrap();
                 },
                 Err(TryRecvError::Empty) => {
                     // queue is empty, nothing to do. let's see if we can create a new miner
@@ -66,4 +89,63 @@ impl OgreThread {
         }
 
     }
+
+    /// Mine at the current difficulty, starting over whenever it changes mid search
+    fn mine(&mut self, miner: &mut Miner) {
+        loop {
+            let difficulty = self.difficulty;
+            let cancel = AtomicBool::new(false);
+            let done = AtomicBool::new(false);
+            let mined = std::thread::scope(|s| {
+                let watcher = s.spawn(|| {
+                    while !done.load(Ordering::Relaxed) {
+                        if latest_difficulty(&self.stats).is_some_and(|latest| latest != difficulty) {
+                            cancel.store(true, Ordering::Relaxed);
+                            return;
+                        }
+                        std::thread::park_timeout(DIFFICULTY_POLL);
+                    }
+                });
+                let mined = miner.mine(difficulty, self.backend.as_ref(), &cancel);
+                done.store(true, Ordering::Relaxed);
+                watcher.thread().unpark();
+                mined
+            });
+            if mined {
+                return;
+            }
+            log::debug!("thread {} restarting miner {}, difficulty changed", self.id, miner.pubkey);
+            self.follow_difficulty();
+        }
+    }
+
+    /// Pick up a new difficulty from stats. Solutions waiting in the loaded queue that no longer
+    /// meet it are dropped and their miners sent back to be mined again.
+    fn follow_difficulty(&mut self) {
+        let Some(difficulty) = latest_difficulty(&self.stats) else {
+            return;
+        };
+        if difficulty == self.difficulty {
+            return;
+        }
+        log::info!("thread {} difficulty changed from {} to {}", self.id, self.difficulty, difficulty);
+        self.difficulty = difficulty;
+        let mut requeued = 0;
+        for _ in 0..self.lq_receiver.len() {
+            let Ok(mut miner) = self.lq_receiver.try_recv() else {
+                break;
+            };
+            if miner.revalidate(&difficulty) {
+                self.lq.send(miner).unwrap();
+                continue;
+            }
+            self.store.lock().unwrap().record_solution(&miner);
+            self.stats.lock().unwrap().update_miner(&miner);
+            self.mq_sender.send(miner).unwrap();
+            requeued += 1;
+        }
+        if requeued > 0 {
+            log::info!("thread {} sent {} miners back to mining, their solutions miss the new difficulty", self.id, requeued);
+        }
+    }
 }
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use log::Level;
+use serde::Deserialize;
+use solana_program::pubkey::Pubkey;
+
+use crate::{
+    config::{MINT, PRICE_MAX_AGE, PRICE_SOURCES, SOL_MINT},
+    stats::{notify, Stats},
+};
+
+/// USD prices as reported by one source. A source may only know one of them.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Prices {
+    pub ore: Option<f64>,
+    pub sol: Option<f64>,
+}
+
+/// Something that knows the USD price of ORE and/or SOL
+#[async_trait]
+pub trait PriceOracle: Send + Sync {
+    fn name(&self) -> &str;
+    async fn fetch(&self, client: &reqwest::Client) -> Result<Prices, String>;
+}
+
+/// Price sources, configured in PRICE_SOURCES
+#[derive(Debug, Clone, Copy)]
+pub enum PriceSource {
+    Jupiter,
+    DexScreener,
+    /// coingecko ids of ore and sol
+    CoinGecko { ore: &'static str, sol: &'static str },
+    /// fixed prices, e.g. as a fallback when every api is down
+    #[allow(dead_code)]
+    Static { ore: Option<f64>, sol: Option<f64> },
+}
+
+impl PriceSource {
+    pub fn oracle(&self) -> Box<dyn PriceOracle> {
+        match *self {
+            PriceSource::Jupiter => Box::new(Jupiter),
+            PriceSource::DexScreener => Box::new(DexScreener),
+            PriceSource::CoinGecko { ore, sol } => Box::new(CoinGecko { ore, sol }),
+            PriceSource::Static { ore, sol } => Box::new(Static(Prices { ore, sol })),
+        }
+    }
+}
+
+fn positive(price: f64) -> Option<f64> {
+    (price.is_finite() && price > 0.0).then_some(price)
+}
+
+pub struct Jupiter;
+
+#[derive(Deserialize)]
+struct JupiterPrice {
+    price: f64,
+}
+
+#[derive(Deserialize)]
+struct JupiterResponse {
+    data: HashMap<String, JupiterPrice>,
+}
+
+#[async_trait]
+impl PriceOracle for Jupiter {
+    fn name(&self) -> &str {
+        "jupiter"
+    }
+
+    async fn fetch(&self, client: &reqwest::Client) -> Result<Prices, String> {
+        let url = format!("https://price.jup.ag/v4/price?ids={},{}", MINT, SOL_MINT);
+        let res = client.get(url).send().await.and_then(|r| r.error_for_status()).map_err(|e| e.to_string())?;
+        let data = res.json::<JupiterResponse>().await.map_err(|e| e.to_string())?.data;
+        Ok(Prices {
+            ore: data.get(&MINT.to_string()).and_then(|p| positive(p.price)),
+            sol: data.get(&SOL_MINT.to_string()).and_then(|p| positive(p.price)),
+        })
+    }
+}
+
+pub struct DexScreener;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DexScreenerToken {
+    address: String,
+}
+
+#[derive(Deserialize)]
+struct DexScreenerLiquidity {
+    usd: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DexScreenerPair {
+    base_token: DexScreenerToken,
+    price_usd: Option<String>,
+    liquidity: Option<DexScreenerLiquidity>,
+}
+
+#[derive(Deserialize)]
+struct DexScreenerResponse {
+    pairs: Option<Vec<DexScreenerPair>>,
+}
+
+impl DexScreener {
+    /// Price of `mint` in its most liquid pair
+    async fn price(client: &reqwest::Client, mint: &Pubkey) -> Result<Option<f64>, String> {
+        let url = format!("https://api.dexscreener.com/latest/dex/tokens/{}", mint);
+        let res = client.get(url).send().await.and_then(|r| r.error_for_status()).map_err(|e| e.to_string())?;
+        let pairs = res.json::<DexScreenerResponse>().await.map_err(|e| e.to_string())?.pairs.unwrap_or_default();
+        let liquidity = |p: &DexScreenerPair| p.liquidity.as_ref().and_then(|l| l.usd).unwrap_or(0.0);
+        Ok(pairs.iter()
+            .filter(|p| p.base_token.address == mint.to_string())
+            .max_by(|a, b| liquidity(a).total_cmp(&liquidity(b)))
+            .and_then(|p| p.price_usd.as_ref()?.parse().ok())
+            .and_then(positive))
+    }
+}
+
+#[async_trait]
+impl PriceOracle for DexScreener {
+    fn name(&self) -> &str {
+        "dexscreener"
+    }
+
+    async fn fetch(&self, client: &reqwest::Client) -> Result<Prices, String> {
+        Ok(Prices {
+            ore: Self::price(client, &MINT).await?,
+            sol: Self::price(client, &SOL_MINT).await?,
+        })
+    }
+}
+
+pub struct CoinGecko {
+    ore: &'static str,
+    sol: &'static str,
+}
+
+#[derive(Deserialize)]
+struct CoinGeckoPrice {
+    usd: f64,
+}
+
+#[async_trait]
+impl PriceOracle for CoinGecko {
+    fn name(&self) -> &str {
+        "coingecko"
+    }
+
+    async fn fetch(&self, client: &reqwest::Client) -> Result<Prices, String> {
+        let url = format!("https://api.coingecko.com/api/v3/simple/price?ids={},{}&vs_currencies=usd", self.ore, self.sol);
+        let res = client.get(url).send().await.and_then(|r| r.error_for_status()).map_err(|e| e.to_string())?;
+        let data = res.json::<HashMap<String, CoinGeckoPrice>>().await.map_err(|e| e.to_string())?;
+        Ok(Prices {
+            ore: data.get(self.ore).and_then(|p| positive(p.usd)),
+            sol: data.get(self.sol).and_then(|p| positive(p.usd)),
+        })
+    }
+}
+
+pub struct Static(Prices);
+
+#[async_trait]
+impl PriceOracle for Static {
+    fn name(&self) -> &str {
+        "static"
+    }
+
+    async fn fetch(&self, _client: &reqwest::Client) -> Result<Prices, String> {
+        Ok(self.0)
+    }
+}
+
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    // the two middle elements, the same one for odd lengths
+    let n = values.len();
+    Some((values[(n - 1) / 2] + values[n / 2]) / 2.0)
+}
+
+/// Median of all configured sources. Failing sources are skipped; when none answer
+/// the last known price is kept and flagged stale after PRICE_MAX_AGE seconds.
+pub struct Oracle {
+    client: reqwest::Client,
+    sources: Vec<Box<dyn PriceOracle>>,
+}
+
+impl Oracle {
+    pub fn new() -> Self {
+        Oracle {
+            client: reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(10))
+                .build()
+                .unwrap_or_default(),
+            sources: PRICE_SOURCES.iter().map(|s| s.oracle()).collect(),
+        }
+    }
+
+    pub async fn fetch(&self) -> (Prices, Vec<String>) {
+        let results = futures::future::join_all(self.sources.iter().map(|s| s.fetch(&self.client))).await;
+        let mut ore = vec![];
+        let mut sol = vec![];
+        let mut errors = vec![];
+        for (source, result) in self.sources.iter().zip(results) {
+            match result {
+                Ok(prices) => {
+                    ore.extend(prices.ore);
+                    sol.extend(prices.sol);
+                }
+                Err(err) => errors.push(format!("{}: {}", source.name(), err)),
+            }
+        }
+        (Prices { ore: median(ore), sol: median(sol) }, errors)
+    }
+
+    /// Fetch and store the prices in `stats`
+    pub async fn update(&self, stats: &std::sync::Mutex<Stats>) {
+        let (prices, errors) = self.fetch().await;
+        let now = chrono::Utc::now().timestamp();
+        let mut stats = stats.lock().unwrap();
+        for err in errors {
+            notify!(stats, Level::Debug, "[oracle] {}", err);
+        }
+        if let Some(price) = prices.ore {
+            stats.oldprice = stats.price;
+            stats.price = price;
+            stats.price_updated = now;
+        }
+        if let Some(price) = prices.sol {
+            stats.sol_price = price;
+            stats.sol_price_updated = now;
+        }
+        let stale = now - stats.price_updated.min(stats.sol_price_updated) > PRICE_MAX_AGE;
+        if stale && !stats.price_stale {
+            notify!(stats, Level::Warn, "[oracle] prices are stale, no source answered for {}s", PRICE_MAX_AGE);
+        } else if !stale && stats.price_stale {
+            notify!(stats, Level::Info, "[oracle] prices are fresh again");
+        }
+        stats.price_stale = stale;
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::HashSet;
+
+use oreprog::anchor_lang::error::ErrorCode;
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig};
+use solana_program::{address_lookup_table::state::AddressLookupTable, instruction::InstructionError, pubkey::Pubkey};
+use solana_sdk::{commitment_config::CommitmentConfig, transaction::{Transaction, TransactionError}};
+
+use crate::{
+    authority::{self, Authority},
+    config::{FEE_PAYER, GENESIS_HASH, HASH_BACKEND, ORE_COLLECTIVE, RPC},
+    daemon::{EXIT_CONFIG, EXIT_SOFTWARE, EXIT_UNAVAILABLE},
+    keystore::read_keypair,
+    utils::{miner_pubkey, pair_pubkey, proof_pubkey, registration_ix},
+};
+
+/// Outcome of one startup check
+struct Check {
+    name: &'static str,
+    result: Result<String, String>,
+    /// exit code if this check fails
+    code: i32,
+}
+
+impl Check {
+    fn config(name: &'static str, result: Result<String, String>) -> Self {
+        Check { name, result, code: EXIT_CONFIG }
+    }
+}
+
+/// Reading a keystore here unlocks it for the rest of the run
+fn keypair(path: &str) -> Result<Pubkey, String> {
+    read_keypair(path).map(|k| pair_pubkey(&k)).map_err(|err| format!("can't read {}: {}", path, err))
+}
+
+async fn program(client: &RpcClient, id: &Pubkey) -> Result<String, String> {
+    match client.get_account(id).await {
+        Ok(account) if account.executable => Ok(format!("{} deployed", id)),
+        Ok(_) => Err(format!("{} is not executable", id)),
+        Err(err) => Err(format!("{} not found: {}", id, err)),
+    }
+}
+
+/// With a fee payer, miners are registered through the collective program's register_with_payer.
+/// Simulating one tells whether the deployed program has it: anchor answers an unknown instruction
+/// with InstructionFallbackNotFound, anything else means it was dispatched.
+async fn register_with_payer(client: &RpcClient, authority: Pubkey, payer: Pubkey) -> Result<String, String> {
+    if payer == authority {
+        return Ok("fee payer is the authority, registering with register".to_string());
+    }
+    let (miner, _) = miner_pubkey(authority, 0);
+    let tx = Transaction::new_with_payer(&[registration_ix(authority, payer, miner, 0)], Some(&payer));
+    let config = RpcSimulateTransactionConfig { sig_verify: false, replace_recent_blockhash: true, ..Default::default() };
+    let result = client.simulate_transaction_with_config(&tx, config).await
+        .map_err(|err| format!("can't simulate register_with_payer: {}", err))?;
+    match result.value.err {
+        Some(TransactionError::InstructionError(_, InstructionError::Custom(code))) if code == ErrorCode::InstructionFallbackNotFound as u32 =>
+            Err(format!("{} has no register_with_payer, deploy the current collective program or unset FEE_PAYER", ORE_COLLECTIVE)),
+        _ => Ok(format!("{} supports register_with_payer", ORE_COLLECTIVE)),
+    }
+}
+
+/// The authority's ALT has to hold every miner and proof account, or transactions won't fit
+async fn lookup_table(client: &RpcClient, config: &Authority, authority: Pubkey) -> Result<String, String> {
+    let alt = config.alt;
+    let account = client.get_account(&alt).await.map_err(|err| format!("{} not found: {}", alt, err))?;
+    let table = AddressLookupTable::deserialize(&account.data).map_err(|err| format!("{} is not a lookup table: {}", alt, err))?;
+    let addresses = table.addresses.iter().collect::<HashSet<_>>();
+    let missing = (0..config.miners)
+        .flat_map(|id| {
+            let (miner, _) = miner_pubkey(authority, id);
+            [miner, proof_pubkey(miner)]
+        })
+        .filter(|key| !addresses.contains(key))
+        .count();
+    if missing == 0 {
+        Ok(format!("{} holds all {} miner and proof accounts", alt, 2 * config.miners as usize))
+    } else {
+        Err(format!("{} is missing {} of {} miner and proof accounts", alt, missing, 2 * config.miners as usize))
+    }
+}
+
+/// Check keypairs, rpc, cluster, lookup table and programs before mining.
+/// Prints a report and returns the exit code to stop with if anything is off.
+pub async fn run() -> Result<(), i32> {
+    let mut checks = vec![];
+
+    let authorities = authority::all().iter()
+        .map(|a| (a, keypair(a.keypair)))
+        .collect::<Vec<_>>();
+    for (a, pubkey) in &authorities {
+        checks.push(Check::config("authority", pubkey.clone().map(|pk| format!("{} from {}", pk, a.keypair))));
+    }
+    let fee_payer = FEE_PAYER.map(|path| (path, keypair(path)));
+    if let Some((path, pubkey)) = &fee_payer {
+        checks.push(Check::config("fee payer", pubkey.clone().map(|pk| format!("{} from {}", pk, path))));
+    }
+    let backend = HASH_BACKEND.backend();
+    let hasher = backend.self_check().map(|_| backend.name()).map_err(|err| format!("{}: {}", backend.name(), err));
+    checks.push(Check { name: "hasher", result: hasher, code: EXIT_SOFTWARE });
+
+    let client = RpcClient::new_with_commitment(RPC.to_string(), CommitmentConfig::confirmed());
+    let reachable = client.get_version().await
+        .map(|v| format!("{} (solana {})", RPC, v.solana_core))
+        .map_err(|err| format!("{} unreachable: {}", RPC, err));
+    let online = reachable.is_ok();
+    checks.push(Check { name: "rpc", result: reachable, code: EXIT_UNAVAILABLE });
+
+    if online {
+        let genesis = match client.get_genesis_hash().await {
+            Ok(hash) if hash.to_string() == GENESIS_HASH => Ok(format!("{}", hash)),
+            Ok(hash) => Err(format!("{} is not the expected cluster {}", hash, GENESIS_HASH)),
+            Err(err) => Err(err.to_string()),
+        };
+        checks.push(Check::config("cluster", genesis));
+        checks.push(Check::config("collective program", program(&client, &ORE_COLLECTIVE).await));
+        checks.push(Check::config("ore program", program(&client, &ore::ID).await));
+        if let (Some((_, Ok(payer))), Some((_, Ok(authority)))) = (&fee_payer, authorities.first()) {
+            checks.push(Check::config("register with payer", register_with_payer(&client, *authority, *payer).await));
+        }
+        for (a, pubkey) in &authorities {
+            if let Ok(pubkey) = pubkey {
+                checks.push(Check::config("lookup table", lookup_table(&client, a, *pubkey).await));
+            }
+        }
+    }
+
+    for check in &checks {
+        match &check.result {
+            Ok(message) => println!("  ok    {:<20} {}", check.name, message),
+            Err(message) => println!("  FAIL  {:<20} {}", check.name, message),
+        }
+    }
+    match checks.iter().find(|c| c.result.is_err()) {
+        Some(failed) => {
+            for check in checks.iter().filter(|c| c.result.is_err()) {
+                log::error!("preflight {} failed: {}", check.name, check.result.as_ref().unwrap_err());
+            }
+            Err(failed.code)
+        }
+        None => Ok(()),
+    }
+}
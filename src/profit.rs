@@ -0,0 +1,63 @@
+use solana_program::native_token::LAMPORTS_PER_SOL;
+
+use crate::{
+    config::{CU_LIMIT_MINE, CU_LIMIT_TRANSFER, INCLUDE_TIP, LAMPORTS_PER_SIGNATURE, ORE_DECIMALS, PROFIT_MARGIN},
+    stats::Stats,
+};
+
+/// What submitters do when a transaction is expected to earn less than PROFIT_MARGIN
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub enum ProfitPolicy {
+    /// keep submitting
+    Ignore,
+    /// hold full batches until it's profitable again. Miners keep hashing.
+    Pause,
+    /// send at most one transaction per thread every this many seconds
+    Throttle(u64),
+}
+
+/// Expected value of sending one transaction with `miners` solutions, in USD
+#[derive(Debug, Clone, Copy)]
+pub struct Profitability {
+    /// chance a sent transaction lands, from this session's sent and landed counts
+    pub landing: f64,
+    /// ore earned if it lands
+    pub reward: f64,
+    /// fee, priority fee and tip paid if it lands
+    pub cost: f64,
+    /// landing * (reward - cost), per sent transaction
+    pub expected: f64,
+}
+
+impl Profitability {
+    /// None while prices are unknown or stale, so a broken price feed never stops submission
+    pub fn estimate(stats: &Stats, miners: usize) -> Option<Self> {
+        if stats.price_stale || stats.price <= 0.0 || stats.sol_price <= 0.0 || stats.rate == 0 {
+            return None;
+        }
+        // a prior of one landing in two tries until we've sent something
+        let landing = (stats.landed_combos as f64 + 1.0) / (stats.sent_sigs as f64 + 2.0);
+        let reward = miners as f64 * stats.rate as f64 / ORE_DECIMALS as f64 * stats.price;
+
+        let mut cu = CU_LIMIT_MINE as u64 * miners as u64;
+        let mut lamports = LAMPORTS_PER_SIGNATURE;
+        if INCLUDE_TIP {
+            cu += CU_LIMIT_TRANSFER as u64;
+            lamports += stats.tip;
+        }
+        lamports += stats.priority_fee * cu / 1_000_000;
+        let cost = lamports as f64 / LAMPORTS_PER_SOL as f64 * stats.sol_price;
+
+        Some(Profitability {
+            landing,
+            reward,
+            cost,
+            expected: landing * (reward - cost),
+        })
+    }
+
+    pub fn profitable(&self) -> bool {
+        self.expected >= PROFIT_MARGIN
+    }
+}
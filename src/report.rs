@@ -0,0 +1,182 @@
+use std::{collections::BTreeMap, io};
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use clap::{Args, ValueEnum};
+use comfy_table::{presets::UTF8_BORDERS_ONLY, Attribute, Cell, Table};
+use serde::Serialize;
+use solana_program::native_token::LAMPORTS_PER_SOL;
+
+use crate::{config::{LEDGER_FILE, ORE_DECIMALS}, ledger::{Record, Status}};
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Format {
+    Table,
+    Csv,
+    Json,
+}
+
+#[derive(Args, Debug)]
+pub struct ReportArgs {
+    /// First day to include (YYYY-MM-DD, UTC)
+    #[arg(long)]
+    pub since: Option<NaiveDate>,
+    /// Last day to include (YYYY-MM-DD, UTC)
+    #[arg(long)]
+    pub until: Option<NaiveDate>,
+    #[arg(long, value_enum, default_value = "table")]
+    pub format: Format,
+    /// Ledger file to read
+    #[arg(long, default_value = LEDGER_FILE)]
+    pub ledger: String,
+}
+
+/// Aggregated numbers for one day or one miner
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Row {
+    pub group: String,
+    pub key: String,
+    pub sent: u64,
+    pub landed: u64,
+    pub landing_rate: f64,
+    pub ore: f64,
+    pub fees_sol: f64,
+    pub tips_sol: f64,
+    pub registrations_sol: f64,
+    pub ore_usd: f64,
+    pub costs_usd: f64,
+    pub pnl_usd: f64,
+}
+
+impl Row {
+    fn new(group: &str, key: String) -> Self {
+        Row { group: group.to_string(), key, ..Default::default() }
+    }
+
+    /// Add a `share` of a ledger record to this row
+    fn add(&mut self, record: &Record, share: f64) {
+        match record.status {
+            Status::Sent => self.sent += 1,
+            Status::Confirmed => {
+                let ore = record.ore.unwrap_or(0) as f64 * share / ORE_DECIMALS as f64;
+                let fees = record.fee.unwrap_or(0) as f64 * share / LAMPORTS_PER_SOL as f64;
+                let tips = record.tip as f64 * share / LAMPORTS_PER_SOL as f64;
+                let registrations = record.registration.unwrap_or(0) as f64 * share / LAMPORTS_PER_SOL as f64;
+                let sol_price = record.sol_price.unwrap_or(0.0);
+                self.landed += 1;
+                self.ore += ore;
+                self.fees_sol += fees;
+                self.tips_sol += tips;
+                self.registrations_sol += registrations;
+                self.ore_usd += ore * record.ore_price.unwrap_or(0.0);
+                self.costs_usd += (fees + tips + registrations) * sol_price;
+            }
+            Status::Failed => {
+                let fees = record.fee.unwrap_or(0) as f64 * share / LAMPORTS_PER_SOL as f64;
+                self.fees_sol += fees;
+                self.costs_usd += fees * record.sol_price.unwrap_or(0.0);
+            }
+        }
+        self.pnl_usd = self.ore_usd - self.costs_usd;
+        self.landing_rate = if self.sent > 0 { self.landed as f64 / self.sent as f64 } else { 0.0 };
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub days: Vec<Row>,
+    pub miners: Vec<Row>,
+    pub total: Row,
+}
+
+/// Read all ledger records sent within the given days
+pub fn read_ledger(path: &str, since: Option<NaiveDate>, until: Option<NaiveDate>) -> Result<Vec<Record>, csv::Error> {
+    let since = since.map(|d| Utc.from_utc_datetime(&d.and_hms_opt(0, 0, 0).unwrap()).timestamp() as u64);
+    let until = until.map(|d| Utc.from_utc_datetime(&d.and_hms_opt(23, 59, 59).unwrap()).timestamp() as u64);
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_path(path)?;
+    let mut records = vec![];
+    for record in reader.deserialize::<Record>() {
+        let record = record?;
+        if since.is_some_and(|s| record.timestamp < s) || until.is_some_and(|u| record.timestamp > u) {
+            continue;
+        }
+        records.push(record);
+    }
+    Ok(records)
+}
+
+pub fn build(records: &[Record]) -> Report {
+    let mut days: BTreeMap<String, Row> = BTreeMap::new();
+    let mut miners: BTreeMap<String, Row> = BTreeMap::new();
+    let mut total = Row::new("total", "all".to_string());
+    for record in records {
+        let day = Utc.timestamp_opt(record.timestamp as i64, 0).unwrap().format("%Y-%m-%d").to_string();
+        days.entry(day.clone()).or_insert_with(|| Row::new("day", day)).add(record, 1.0);
+        total.add(record, 1.0);
+        // miners in one transaction earn the same reward, so split it evenly
+        let keys: Vec<&str> = record.miners.split_whitespace().collect();
+        for key in &keys {
+            miners.entry(key.to_string())
+                .or_insert_with(|| Row::new("miner", key.to_string()))
+                .add(record, 1.0 / keys.len() as f64);
+        }
+    }
+    Report {
+        days: days.into_values().collect(),
+        miners: miners.into_values().collect(),
+        total,
+    }
+}
+
+fn table(title: &str, rows: &[Row]) -> Table {
+    let mut table = Table::new();
+    table.load_preset(UTF8_BORDERS_ONLY);
+    table.set_content_arrangement(comfy_table::ContentArrangement::DynamicFullWidth);
+    table.set_header(vec![
+        Cell::new(title).add_attribute(Attribute::Bold),
+        Cell::new("Sent").add_attribute(Attribute::Bold),
+        Cell::new("Landed").add_attribute(Attribute::Bold),
+        Cell::new("Rate").add_attribute(Attribute::Bold),
+        Cell::new("Ore").add_attribute(Attribute::Bold),
+        Cell::new("Fees").add_attribute(Attribute::Bold),
+        Cell::new("Tips").add_attribute(Attribute::Bold),
+        Cell::new("Registrations").add_attribute(Attribute::Bold),
+        Cell::new("P&L").add_attribute(Attribute::Bold),
+    ]);
+    for row in rows {
+        table.add_row(vec![
+            Cell::new(&row.key),
+            Cell::new(format!("{}", row.sent)),
+            Cell::new(format!("{}", row.landed)),
+            Cell::new(format!("{:.2}%", row.landing_rate * 100.0)),
+            Cell::new(format!("{:.4} (${:.2})", row.ore, row.ore_usd)),
+            Cell::new(format!("{:.6}", row.fees_sol)),
+            Cell::new(format!("{:.6}", row.tips_sol)),
+            Cell::new(format!("{:.6}", row.registrations_sol)),
+            Cell::new(format!("${:.2}", row.pnl_usd)),
+        ]);
+    }
+    table
+}
+
+pub fn run(args: ReportArgs) -> Result<(), csv::Error> {
+    let records = read_ledger(&args.ledger, args.since, args.until)?;
+    let report = build(&records);
+    match args.format {
+        Format::Table => {
+            println!("{}", table("Day", &report.days));
+            println!("{}", table("Miner", &report.miners));
+            println!("{}", table("Total", &[report.total]));
+        }
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(io::stdout());
+            for row in report.days.iter().chain(report.miners.iter()).chain([&report.total]) {
+                writer.serialize(row)?;
+            }
+            writer.flush()?;
+        }
+        Format::Json => {
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        }
+    }
+    Ok(())
+}
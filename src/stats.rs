@@ -1,57 +1,250 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use log::Level;
 use ore::state::Hash;
+use serde::{Serialize, Serializer};
+use solana_program::pubkey::Pubkey;
+
+use crate::{budget::Budget, config::NOTIFICATION_LIMIT, miner::{Miner, MinerState}, tip::{TipContext, TipFloor, TipStrategy}};
 
-use crate::config::MAX_MINERS;
+/// Log a message and keep it in the notification log shown on the dashboard.
+/// `$stats` is anything that derefs to `Stats`, usually a locked guard.
+macro_rules! notify {
+    ($stats:expr, $level:expr, $($arg:tt)+) => {{
+        let message = format!($($arg)+);
+        log::log!(target: module_path!(), $level, "{}", message);
+        $stats.notify($level, message);
+    }};
+}
+pub(crate) use notify;
 
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub enum ThreadType {
     Ogre,
     Submitter,
-    Stats,
-    Oracle
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub enum Activity {
     Idle,
-    Premining,
     Mining,
     Accumulating(usize),
     Sending(usize),
+    /// holding a full batch while submission is paused
+    Paused(usize),
+}
+
+impl Activity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Activity::Idle => "idle",
+            Activity::Mining => "mining",
+            Activity::Accumulating(_) => "accumulating",
+            Activity::Sending(_) => "sending",
+            Activity::Paused(_) => "paused",
+        }
+    }
+
+    /// Number of miners held while accumulating or sending
+    pub fn batch(&self) -> usize {
+        match self {
+            Activity::Accumulating(n) | Activity::Sending(n) | Activity::Paused(n) => *n,
+            _ => 0,
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct ThreadStatus {
     pub id: usize,
     pub ttype: ThreadType,
     pub activity: Activity,
 }
 
-#[derive(Debug)]
+/// SOL spent on landed transactions, in lamports
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct Costs {
+    /// transaction fee as charged, including the priority fee
+    pub fee: u64,
+    /// priority fee part of `fee`
+    pub priority: u64,
+    pub tip: u64,
+    /// registration fee and proof rent of newly registered miners
+    pub registration: u64,
+}
+
+impl Costs {
+    pub fn total(&self) -> u64 {
+        self.fee + self.tip + self.registration
+    }
+
+    pub fn add(&mut self, other: &Costs) {
+        self.fee += other.fee;
+        self.priority += other.priority;
+        self.tip += other.tip;
+        self.registration += other.registration;
+    }
+}
+
+/// Last known state of a single miner
+#[derive(Debug, Clone, Serialize)]
+pub struct MinerStatus {
+    pub id: u8,
+    pub pubkey: String,
+    pub state: &'static str,
+    pub nonce: Option<u64>,
+    pub total_hashes: u64,
+    pub total_rewards: u64,
+    /// unix timestamp of the last landed solution this session
+    pub last_landed: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    /// unix timestamp
+    pub time: i64,
+    pub level: Level,
+    pub message: String,
+}
+
+fn serialize_difficulty<S: Serializer>(difficulty: &Option<Hash>, serializer: S) -> Result<S::Ok, S::Error> {
+    match difficulty {
+        Some(hash) => serializer.serialize_some(&solana_sdk::keccak::Hash::from(*hash).to_string()),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[derive(Debug, Serialize)]
 pub struct Stats {
     pub miners: u64,
     pub hashes_mined: u64,
     pub hashes_submitted: u64,
     pub ore_mined: u64,
     pub sol_spent: u64,
+    /// breakdown of `sol_spent`
+    pub costs: Costs,
     pub lamports: u64,
+    /// balance of the wallets paying fees, FEE_PAYER or each authority
+    pub balance: u64,
     pub lifetime_hashes: u64,
     pub lifetime_rewards: u64,
     pub sent_sigs: u64,
+    /// signatures that failed to send or expired unconfirmed
+    pub failed_sigs: u64,
+    pub landed_combos: u64,
     pub threads: Vec<ThreadStatus>,
+    /// ore price in USD
     pub price: f64,
     pub oldprice: f64,
+    /// sol price in USD
+    pub sol_price: f64,
+    /// unix timestamps of the last price updates
+    pub price_updated: i64,
+    pub sol_price_updated: i64,
+    /// no price source answered for PRICE_MAX_AGE seconds
+    pub price_stale: bool,
     pub supply: f64,
     pub oldsupply: f64,
     pub rate: u64,
     pub oldrate: u64,
     pub tip: u64,
+    /// decides `tip` as combos land or don't
+    #[serde(skip)]
+    pub tip_strategy: Box<dyn TipStrategy>,
+    /// recently landed jito tips
+    pub tip_floor: Option<TipFloor>,
+    /// priority fee in micro-lamports per CU, adjustable at runtime
+    pub priority_fee: u64,
+    /// stop sending transactions, while still mining
+    pub paused: bool,
+    /// expected profit per transaction is below PROFIT_MARGIN
+    pub unprofitable: bool,
+    /// spending in the current budget windows
+    pub budget: Budget,
+    /// the same per authority pubkey
+    pub authority_budgets: BTreeMap<String, Budget>,
+    /// authorities held back because a budget ran out, with the unix timestamp it resets
+    pub over_budget: BTreeMap<String, i64>,
+    #[serde(serialize_with = "serialize_difficulty")]
     pub difficulty: Option<Hash>,
-    pub notifications: Vec<String>,
+    #[serde(skip)]
+    pub miner_status: BTreeMap<String, MinerStatus>,
+    /// most recent NOTIFICATION_LIMIT notifications
+    #[serde(skip)]
+    pub notifications: VecDeque<Notification>,
 }
 
 impl Stats {
-    pub fn can_make_more_miners(&self) -> bool {
-        self.miners < MAX_MINERS as u64
+    /// Keep a notification, dropping the oldest once NOTIFICATION_LIMIT is reached.
+    /// Use the `notify!` macro to also log it.
+    pub fn notify(&mut self, level: Level, message: String) {
+        if self.notifications.len() >= NOTIFICATION_LIMIT {
+            self.notifications.pop_front();
+        }
+        self.notifications.push_back(Notification {
+            time: chrono::Utc::now().timestamp(),
+            level,
+            message,
+        });
+    }
+
+    pub fn update_miner(&mut self, miner: &Miner) {
+        let (state, nonce) = match miner.state {
+            MinerState::New(_) => ("new", None),
+            MinerState::Premined(nonce, _) => ("premined", Some(nonce)),
+            MinerState::Minable(_) => ("minable", None),
+            MinerState::Loaded(nonce, _) => ("loaded", Some(nonce)),
+        };
+        let last_landed = self.miner_status.get(&miner.pubkey.to_string()).and_then(|m| m.last_landed);
+        self.miner_status.insert(miner.pubkey.to_string(), MinerStatus {
+            id: miner.id,
+            pubkey: miner.pubkey.to_string(),
+            state,
+            nonce,
+            total_hashes: miner.total_hashes,
+            total_rewards: miner.total_rewards,
+            last_landed,
+        });
+    }
+
+    pub fn miner_landed(&mut self, miner: &Miner) {
+        self.update_miner(miner);
+        if let Some(status) = self.miner_status.get_mut(&miner.pubkey.to_string()) {
+            status.last_landed = Some(chrono::Utc::now().timestamp());
+        }
+    }
+
+
+    pub fn tip_context(&self, miners: usize) -> TipContext {
+        TipContext {
+            tip: self.tip,
+            miners,
+            rate: self.rate,
+            ore_price: self.price,
+            sol_price: self.sol_price,
+            tip_floor: self.tip_floor,
+        }
     }
+
+    pub fn add_costs(&mut self, authority: &Pubkey, costs: &Costs) {
+        let now = chrono::Utc::now().timestamp();
+        self.costs.add(costs);
+        self.sol_spent += costs.total();
+        self.budget.spend(costs.total(), now);
+        self.authority_budgets.entry(authority.to_string()).or_default().spend(costs.total(), now);
+    }
+
+    /// Hold `lamports` of the global and the authority's budgets for a transaction in flight
+    pub fn reserve(&mut self, authority: &Pubkey, lamports: u64) {
+        self.budget.reserve(lamports);
+        self.authority_budgets.entry(authority.to_string()).or_default().reserve(lamports);
+    }
+
+    /// Give back a reservation once its transaction landed or expired
+    pub fn release(&mut self, authority: &Pubkey, lamports: u64) {
+        self.budget.release(lamports);
+        self.authority_budgets.entry(authority.to_string()).or_default().release(lamports);
+    }
+
 }
@@ -0,0 +1,203 @@
+use std::{collections::{BTreeMap, HashMap}, fs, io, path::PathBuf, str::FromStr, sync::{Arc, Mutex}, time::Duration};
+
+use log::Level;
+use serde::{Deserialize, Serialize};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{keccak::{hashv, Hash}, signature::Signature};
+
+use crate::{budget::Budget, config::{STATE_FLUSH_INTERVAL, STATE_HISTORY}, miner::{Miner, MinerState}, stats::{notify, Stats}};
+
+/// A solution that was hashed but has not landed yet
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PendingSolution {
+    pub nonce: u64,
+    pub hash: String,
+}
+
+/// A landed submission for a miner
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Submission {
+    pub signature: String,
+    pub timestamp: i64,
+    pub proof_hash: String,
+    pub nonce: u64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct MinerRecord {
+    pub id: u8,
+    /// Last known proof hash. For unregistered miners this is the premining seed.
+    pub proof_hash: String,
+    /// Solution for `proof_hash` that still has to be submitted
+    pub pending: Option<PendingSolution>,
+    pub history: Vec<Submission>,
+}
+
+/// Spending in the budget windows, kept so a restart doesn't reset them
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Budgets {
+    pub global: Budget,
+    /// per authority pubkey
+    pub authorities: BTreeMap<String, Budget>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    miners: HashMap<String, MinerRecord>,
+    #[serde(default)]
+    budgets: Budgets,
+}
+
+/// State files written before budgets were kept hold just the miners
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StateFile {
+    State(State),
+    Miners(HashMap<String, MinerRecord>),
+}
+
+/// Local miner state, keyed by miner pubkey, and budget spending, persisted as json.
+#[derive(Debug)]
+pub struct Store {
+    path: PathBuf,
+    state: State,
+    /// changed since the last save
+    dirty: bool,
+}
+
+impl Store {
+    /// Open the store at `path`. A missing file yields an empty store. A file that doesn't parse
+    /// is moved aside to `<path>.corrupt` so the next save can't destroy it, and an empty store is
+    /// used instead. Any other read error is returned.
+    pub fn open(path: &str) -> Result<Self, io::Error> {
+        let path = PathBuf::from(path);
+        let state = match fs::read_to_string(&path) {
+            Ok(data) => match serde_json::from_str(&data) {
+                Ok(StateFile::State(state)) => state,
+                Ok(StateFile::Miners(miners)) => State { miners, ..Default::default() },
+                Err(err) => {
+                    let corrupt = PathBuf::from(format!("{}.corrupt", path.display()));
+                    fs::rename(&path, &corrupt)?;
+                    log::error!("{} is corrupt ({}), moved it to {} and starting empty", path.display(), err, corrupt.display());
+                    State::default()
+                }
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => State::default(),
+            Err(err) => return Err(err),
+        };
+        Ok(Store { path, state, dirty: false })
+    }
+
+    pub fn get(&self, pubkey: &Pubkey) -> Option<&MinerRecord> {
+        self.state.miners.get(&pubkey.to_string())
+    }
+
+    /// Write the store to disk. Writes to a temporary file first so a crash never leaves a
+    /// truncated state file behind.
+    pub fn save(&mut self) -> Result<(), io::Error> {
+        let data = serde_json::to_string_pretty(&self.state)?;
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &self.path)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Save if anything changed since the last save, reporting a failure as a notification. The
+    /// in-memory state stays authoritative, the next flush tries again.
+    pub fn flush(&mut self, stats: &Mutex<Stats>) {
+        if !self.dirty {
+            return;
+        }
+        if let Err(err) = self.save() {
+            let mut stats = stats.lock().unwrap();
+            notify!(stats, Level::Error, "failed to save {}: {}", self.path.display(), err);
+        }
+    }
+
+    pub fn budgets(&self) -> &Budgets {
+        &self.state.budgets
+    }
+
+    /// Remember the spending in the current budget windows
+    pub fn record_budgets(&mut self, stats: &Stats) {
+        self.state.budgets = Budgets { global: stats.budget, authorities: stats.authority_budgets.clone() };
+        self.dirty = true;
+    }
+
+    fn record(&mut self, miner: &Miner) -> &mut MinerRecord {
+        self.dirty = true;
+        self.state.miners.entry(miner.pubkey.to_string()).or_insert_with(|| MinerRecord {
+            id: miner.id,
+            ..Default::default()
+        })
+    }
+
+    /// Remember the current state of a miner, including any solution that is ready to submit.
+    pub fn record_solution(&mut self, miner: &Miner) {
+        let record = self.record(miner);
+        record.proof_hash = miner.challenge.to_string();
+        record.pending = match miner.state {
+            MinerState::Premined(nonce, hash) | MinerState::Loaded(nonce, hash) => Some(PendingSolution {
+                nonce,
+                hash: hash.to_string(),
+            }),
+            MinerState::New(_) | MinerState::Minable(_) => None,
+        };
+    }
+
+    /// A combo including `miner` landed with `sig`. `proof_hash` is the new on-chain hash.
+    pub fn record_landed(&mut self, miner: &Miner, sig: &Signature, proof_hash: Hash) {
+        let record = self.record(miner);
+        if let Some(pending) = record.pending.take() {
+            record.history.push(Submission {
+                signature: sig.to_string(),
+                timestamp: chrono::Utc::now().timestamp(),
+                proof_hash: record.proof_hash.clone(),
+                nonce: pending.nonce,
+            });
+        }
+        if record.history.len() > STATE_HISTORY {
+            let excess = record.history.len() - STATE_HISTORY;
+            record.history.drain(..excess);
+        }
+        record.proof_hash = proof_hash.to_string();
+    }
+
+    /// Restore a stored solution into a freshly loaded miner, if it was computed against the
+    /// miner's current on-chain proof hash and still satisfies `difficulty`.
+    /// Returns true if the miner now holds a solution ready to submit.
+    pub fn restore(&self, miner: &mut Miner, difficulty: Hash) -> bool {
+        let Some(record) = self.get(&miner.pubkey) else {
+            return false;
+        };
+        let Some(pending) = record.pending.as_ref() else {
+            return false;
+        };
+        if record.proof_hash != miner.challenge.to_string() {
+            return false;
+        }
+        let hash = hashv(&[
+            miner.challenge.as_ref(),
+            miner.pubkey.as_ref(),
+            pending.nonce.to_le_bytes().as_slice(),
+        ]);
+        if Hash::from_str(&pending.hash).ok() != Some(hash) || hash.gt(&difficulty) {
+            return false;
+        }
+        miner.state = match miner.state {
+            MinerState::New(_) => MinerState::Premined(pending.nonce, hash),
+            MinerState::Minable(_) => MinerState::Loaded(pending.nonce, hash),
+            _ => return false,
+        };
+        true
+    }
+}
+
+/// Write the store every STATE_FLUSH_INTERVAL seconds on a background thread, if it changed
+pub fn spawn_flusher(store: Arc<Mutex<Store>>, stats: Arc<Mutex<Stats>>) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(Duration::from_secs(STATE_FLUSH_INTERVAL));
+        store.lock().unwrap().flush(&stats);
+    });
+}
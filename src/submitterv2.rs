@@ -1,58 +1,56 @@
 use core::fmt;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
 use ore::utils::AccountDeserialize;
-use crossbeam_channel::{unbounded, TryRecvError, Sender, Receiver};
-use ore::state::Bus;
-use ore::{self, BUS_COUNT, BUS_ADDRESSES, BUS_EPOCH_REWARDS};
-use oreprog::anchor_lang::InstructionData;
-use oreprog::constants::{MINER_COLLECTIVE_TREASURY, ORE_PROGRAM_ID};
+use crossbeam_channel::{TryRecvError, Sender, Receiver};
+use ore::state::{Bus, Proof};
+use ore::{self, BUS_ADDRESSES};
+use oreprog::constants::{ORE_PROGRAM_ID, PROOF_RENT, REGISTRATION_FEE};
 use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_client::rpc_config::RpcSendTransactionConfig;
 use solana_program::instruction::{Instruction, AccountMeta};
-use solana_program::message::VersionedMessage;
-use solana_program::native_token::LAMPORTS_PER_SOL;
 use solana_program::pubkey::Pubkey;
 use solana_program::sysvar::slot_hashes;
-use solana_program::{system_instruction, address_lookup_table, system_program};
-use solana_sdk::commitment_config::CommitmentLevel;
-use solana_sdk::signature::{read_keypair_file, Signature, Keypair};
-use solana_sdk::transaction::{Transaction, VersionedTransaction};
+use solana_program::system_instruction;
+use solana_sdk::keccak::Hash;
+use solana_sdk::signature::Signature;
 use solana_sdk::{
     commitment_config::CommitmentConfig,
     compute_budget::ComputeBudgetInstruction,
 };
 
-use solana_client::{client_error::{ClientError, ClientErrorKind, Result}};
+use solana_client::client_error::Result;
 use solana_transaction_status::TransactionConfirmationStatus;
-use crate::config::{CU_LIMIT_MINE, CU_LIMIT_REGISTER, SPAM, FUNDING, CU_LIMIT_TRANSFER, JITO, TIP_ACCOUNT, TIP, ORE_DECIMALS, TIMEOUT, INCLUDE_TIP, RPC, PRIO_FEE, ALT, FUNDING_PK, ORE_TREASURY};
-use crate::stats::{ThreadStatus, ThreadType, Activity};
-use crate::utils::{get_treasury, pair_pubkey, create_tx_with_address_table_lookup, register_ix, mine_ix};
+use crate::config::{CU_LIMIT_MINE, CU_LIMIT_REGISTER, CU_LIMIT_TRANSFER, JITO, TIP_ACCOUNT, TIMEOUT, INCLUDE_TIP, RPC, ORE_TREASURY, LAMPORTS_PER_SIGNATURE, PROFIT_POLICY, BUDGET_HOURLY, BUDGET_DAILY};
+use crate::budget::{self, Budget};
+use crate::authority::{self, Authority};
+use crate::profit::{ProfitPolicy, Profitability};
+use crate::ledger::{self, Record, Status};
+use crate::ogrethread::latest_difficulty;
+use crate::stats::{ThreadStatus, ThreadType, Activity, Costs, notify};
+use crate::utils::{get_treasury, create_tx_with_address_table_lookup, custom_error, registration_ix, signers, mine_ix, get_transaction_meta, TxMeta};
 use crate::{
-    miner::{Miner, MinerState}, utils::get_proof, stats::Stats
+    miner::{Miner, MinerState}, utils::get_proof, stats::Stats, store::Store
 };
 use rand::Rng;
+use log::Level;
 
 use oreprog::{IndexedSolution};
 
-/// csv mining record
-struct Record {
-    signature: String,
-    timestamp: u64,
-    accounts: u64
-}
-
-
 #[derive(Clone)]
 pub struct SigTime {
     sig: Signature,
     time: Instant,
+    /// unix timestamp of sending
+    sent: u64,
+    /// tip paid by this signature, resends may pay a different one
+    tip: u64,
 }
 
 impl SigTime {
-    pub fn new(sig: Signature) -> Self {
-        Self { sig, time: Instant::now() }
+    pub fn new(sig: Signature, tip: u64) -> Self {
+        Self { sig, time: Instant::now(), sent: ledger::now(), tip }
     }
     pub fn expired(&self) -> bool {
         self.time.elapsed() > Duration::from_secs(TIMEOUT)
@@ -66,15 +64,29 @@ impl fmt::Debug for SigTime {
 
 }
 
+/// A signature of the combo that made it into a block
+pub enum Landed {
+    Ok(Signature),
+    /// executed with an error: the fee is charged, nothing was mined
+    Failed(Signature),
+}
 
 pub struct Combo {
+    /// owner of all miners in this combo
+    authority: &'static Authority,
     miners: Vec<Miner>,
     sigs: Vec<SigTime>,
     ixs: Vec<Instruction>,
     stats: Arc<Mutex<Stats>>,
-    has_account_creation: bool,
+    /// number of miners registered by this combo
+    registrations: u64,
     has_tip: bool,
     tip_amount: u64,
+    bus: u64,
+    priority_fee: u64,
+    cu_limit: u32,
+    /// lamports held in the budgets while signatures are in flight
+    reserved: u64,
 }
 
 impl Combo {
@@ -82,12 +94,14 @@ impl Combo {
         // generate CU
         let mut total_cu: u32 = 300; // compute budget progs?
         let mut ixs: Vec<Instruction> = vec![];
-        let mut has_account_creation = false;
+        let mut registrations = 0;
         let mut has_tip = false;
         let mut tip_amount = 0;
 
         let mut ids: Vec<IndexedSolution> = vec![];
         let mut mines: Vec<AccountMeta> = vec![];
+        let authority = authority::find(&miners[0].authority);
+        let payer = authority.fee_payer();
 
         for miner in &miners {
             match miner.state {
@@ -97,14 +111,7 @@ impl Combo {
                 MinerState::Premined(nonce, _hash) => {
                     //let transfer_ix = system_instruction::transfer(&funding_pk, &miner.pubkey, 10_000_000 ); // 0.01 SOL
 
-                    let ix_register = register_ix(oreprog::accounts::Register{
-                        miner: miner.pubkey, 
-                        proof: miner.proof,
-                        authority: FUNDING_PK,
-                        miner_collective_treasury: MINER_COLLECTIVE_TREASURY,
-                        ore: ORE_PROGRAM_ID,
-                        system_program: system_program::id(),
-                    }, miner.id);
+                    let ix_register = registration_ix(authority.pubkey(), payer, miner.pubkey, miner.id);
 
 
                     total_cu += CU_LIMIT_REGISTER + CU_LIMIT_MINE ;
@@ -116,7 +123,7 @@ impl Combo {
                     });
                     mines.push(AccountMeta { pubkey: miner.pubkey, is_signer: false, is_writable: true });
                     mines.push(AccountMeta { pubkey: miner.proof, is_signer: false, is_writable: true });
-                    has_account_creation = true;
+                    registrations += 1;
                 },
                 MinerState::Loaded(nonce, _hash) => {
                     ids.push(IndexedSolution{
@@ -133,9 +140,9 @@ impl Combo {
             }
         }
 
-        if ids.len() > 0 {
+        if !ids.is_empty() {
             let mut ix_mine = mine_ix(oreprog::accounts::Mine{
-                authority: FUNDING_PK,
+                authority: authority.pubkey(),
                 bus: BUS_ADDRESSES[bus.id as usize],
                 treasury: ORE_TREASURY,
                 ore: ORE_PROGRAM_ID,
@@ -148,7 +155,7 @@ impl Combo {
 
         if INCLUDE_TIP {
             let lockstats = stats.lock().unwrap();
-            let tip_ix = system_instruction::transfer(&miners[0].pubkey, &TIP_ACCOUNT, lockstats.tip ); // jito tip
+            let tip_ix = system_instruction::transfer(&payer, &TIP_ACCOUNT, lockstats.tip ); // jito tip
             ixs.push(tip_ix);
             has_tip = true;
             tip_amount = lockstats.tip;
@@ -156,19 +163,121 @@ impl Combo {
         }
 
         let cu_limit_ix = ComputeBudgetInstruction::set_compute_unit_limit(total_cu);
-        let cu_price_ix = ComputeBudgetInstruction::set_compute_unit_price(PRIO_FEE);
+        let priority_fee = stats.lock().unwrap().priority_fee;
+        let cu_price_ix = ComputeBudgetInstruction::set_compute_unit_price(priority_fee);
         
         ixs.insert(0, cu_limit_ix);
         ixs.insert(1, cu_price_ix);
 
         Self {
+            authority,
             miners,
             sigs: vec![],
             ixs,
             stats,
-            has_account_creation,
+            registrations,
             has_tip,
-            tip_amount
+            tip_amount,
+            bus: bus.id,
+            priority_fee,
+            cu_limit: total_cu,
+            reserved: 0,
+        }
+    }
+
+    fn record(&self, sig: &SigTime, status: Status) -> Record {
+        let (ore_price, sol_price) = {
+            let stats = self.stats.lock().unwrap();
+            (stats.price, stats.sol_price)
+        };
+        Record {
+            signature: sig.sig.to_string(),
+            status,
+            timestamp: sig.sent,
+            confirmed_at: None,
+            slot: None,
+            accounts: self.miners.len() as u64,
+            miners: self.miners.iter().map(|m| m.pubkey.to_string()).collect::<Vec<String>>().join(" "),
+            bus: self.bus,
+            priority_fee: self.priority_fee,
+            tip: sig.tip,
+            fee: None,
+            registration: None,
+            ore: None,
+            ore_price: (ore_price > 0.0).then_some(ore_price),
+            sol_price: (sol_price > 0.0).then_some(sol_price),
+        }
+    }
+
+    fn record_sent(&self, sig: &SigTime) {
+        if let Err(err) = ledger::append(&self.record(sig, Status::Sent)) {
+            let mut stats = self.stats.lock().unwrap();
+            notify!(stats, Level::Error, "[Combo::record_sent] {:?}", err);
+        }
+    }
+
+    /// The signature as it was sent
+    fn sent(&self, sig: &Signature) -> SigTime {
+        match self.sigs.iter().find(|s| s.sig == *sig) {
+            Some(s) => s.clone(),
+            None => SigTime::new(*sig, if self.has_tip { self.tip_amount } else { 0 }),
+        }
+    }
+
+    /// Transaction fee and its priority part. Without the transaction meta, they are estimated
+    /// from the compute unit limit.
+    fn fee(&self, meta: Option<&TxMeta>) -> (u64, u64) {
+        match meta {
+            Some(meta) => (meta.fee, meta.priority_fee()),
+            None => {
+                let priority = self.priority_fee * self.cu_limit as u64 / 1_000_000;
+                (LAMPORTS_PER_SIGNATURE + priority, priority)
+            }
+        }
+    }
+
+    /// What this combo cost once `sig` landed
+    pub fn costs(&self, sig: &Signature, meta: Option<&TxMeta>) -> Costs {
+        let (fee, priority) = self.fee(meta);
+        Costs {
+            fee,
+            priority,
+            tip: self.sent(sig).tip,
+            registration: self.registrations * (REGISTRATION_FEE + PROOF_RENT),
+        }
+    }
+
+    /// What a failed transaction cost: only the fee, the tip and registrations are rolled back
+    pub fn failed_costs(&self, meta: Option<&TxMeta>) -> Costs {
+        let (fee, priority) = self.fee(meta);
+        Costs { fee, priority, ..Default::default() }
+    }
+
+    fn record_landed(&self, sig: &Signature, status: Status, meta: Option<&TxMeta>, costs: &Costs) -> Record {
+        let mut record = self.record(&self.sent(sig), status);
+        record.confirmed_at = Some(ledger::now());
+        record.slot = meta.map(|m| m.slot);
+        record.tip = costs.tip;
+        record.fee = Some(costs.fee);
+        record.registration = Some(costs.registration);
+        record
+    }
+
+    /// Write the ledger entry for a landed transaction
+    pub fn record_confirmed(&self, sig: &Signature, meta: Option<&TxMeta>, costs: &Costs, ore: u64) {
+        let mut record = self.record_landed(sig, Status::Confirmed, meta, costs);
+        record.ore = Some(ore);
+        if let Err(err) = ledger::append(&record) {
+            let mut stats = self.stats.lock().unwrap();
+            notify!(stats, Level::Error, "[Combo::record_confirmed] {:?}", err);
+        }
+    }
+
+    /// Write the ledger entry for a transaction that landed with an error
+    pub fn record_failed(&self, sig: &Signature, meta: Option<&TxMeta>, costs: &Costs) {
+        if let Err(err) = ledger::append(&self.record_landed(sig, Status::Failed, meta, costs)) {
+            let mut stats = self.stats.lock().unwrap();
+            notify!(stats, Level::Error, "[Combo::record_failed] {:?}", err);
         }
     }
 
@@ -176,79 +285,54 @@ impl Combo {
         // change the tip amount
         if self.has_tip {
             let stats = self.stats.lock().unwrap();
-            let last_ix = self.ixs.pop().unwrap();
-            let tip_ix = system_instruction::transfer(&self.miners[0].pubkey, &TIP_ACCOUNT, stats.tip ); // jito tip
+            let _last_ix = self.ixs.pop().unwrap();
+            let tip_ix = system_instruction::transfer(&self.authority.fee_payer(), &TIP_ACCOUNT, stats.tip ); // jito tip
             self.ixs.push(tip_ix);
+            self.tip_amount = stats.tip;
         }  
     }
 
-    pub async fn build_send_with_alt(&mut self) -> Result<()> {
-        let client = RpcClient::new_with_commitment(RPC.to_string(), CommitmentConfig::confirmed());
-        let jito = RpcClient::new_with_commitment(JITO.to_string().clone(), CommitmentConfig::confirmed());
-
-
-        let keypair = read_keypair_file(FUNDING).unwrap();
-        let payer = read_keypair_file(FUNDING).unwrap();
-        let keypairs = vec![keypair];
-        let refkeys = keypairs.iter().collect::<Vec<&Keypair>>();
-
-        let (tx, send_cfg) = create_tx_with_address_table_lookup(&client, self.ixs.as_slice(), ALT, &payer, &refkeys).await.unwrap();
+    /// Hold the most this combo can cost at the current tip in the budgets, replacing an earlier
+    /// reservation
+    fn reserve(&mut self, stats: &mut Stats) {
+        let authority = self.authority.pubkey();
+        stats.release(&authority, self.reserved);
+        self.reserved = budget::max_cost(&self.miners, stats);
+        stats.reserve(&authority, self.reserved);
+    }
 
-        match jito.send_transaction_with_config(&tx, send_cfg).await {
-            Ok(sig) => {
-                self.sigs.push(SigTime::new(sig));
-                {
-                    let mut stats = self.stats.lock().unwrap();
-                    stats.sent_sigs += 1;
-                }
-            }
-            Err(err) => {
-                {
-                    let mut stats = self.stats.lock().unwrap();
-                    stats.sent_sigs += 1;
-                    stats.notifications.push(format!("[Combo::build_send] {:?}", err));
-                }
-            }
-        }
-        Ok(())
+    /// Give back the reservation, once the combo landed or all its signatures expired
+    fn release(&mut self, stats: &mut Stats) {
+        stats.release(&self.authority.pubkey(), self.reserved);
+        self.reserved = 0;
     }
 
-    pub async fn build_send(&mut self) -> Result<()> {
+    pub async fn build_send_with_alt(&mut self) -> Result<()> {
         let client = RpcClient::new_with_commitment(RPC.to_string(), CommitmentConfig::confirmed());
         let jito = RpcClient::new_with_commitment(JITO.to_string().clone(), CommitmentConfig::confirmed());
 
 
-        let keypair = read_keypair_file(FUNDING).unwrap();
-        let payer = read_keypair_file(FUNDING).unwrap();
-        let keypairs = vec![keypair];
-        let refkeys = keypairs.iter().collect::<Vec<&Keypair>>();
+        let keypair = self.authority.read_keypair();
+        let payer = self.authority.read_fee_payer();
+        let refkeys = signers(&keypair, &payer);
+        let stats = self.stats.clone();
+        self.reserve(&mut stats.lock().unwrap());
 
-        let (hash, slot) = 
-            match client.get_latest_blockhash_with_commitment(CommitmentConfig::confirmed()).await {
-                Ok(a) => a,
-                Err(err) => {
-                    {
-                        let mut stats = self.stats.lock().unwrap();
-                        stats.sent_sigs += 1;
-                        stats.notifications.push(format!("[Combo::build_send] {:?}", err));
-                    }
-                    return Ok(());
-                }
-            };
-        let send_cfg = RpcSendTransactionConfig {
-            skip_preflight: true,
-            preflight_commitment: Some(CommitmentLevel::Confirmed),
-            encoding: Some(solana_transaction_status::UiTransactionEncoding::Base64),
-            max_retries: Some(1),
-            min_context_slot: Some(slot),
+        let (tx, send_cfg) = match create_tx_with_address_table_lookup(&client, self.ixs.as_slice(), self.authority.alt, &payer, &refkeys).await {
+            Ok(tx) => tx,
+            Err(err) => {
+                let mut stats = stats.lock().unwrap();
+                self.release(&mut stats);
+                notify!(stats, Level::Warn, "[Combo::build_send] {:?}", err);
+                return Ok(());
+            }
         };
 
-        let mut tx = Transaction::new_with_payer(self.ixs.as_slice(), Some(&FUNDING_PK));
-        tx.sign(&refkeys, hash);
-
         match jito.send_transaction_with_config(&tx, send_cfg).await {
             Ok(sig) => {
-                self.sigs.push(SigTime::new(sig));
+                let sig = SigTime::new(sig, if self.has_tip { self.tip_amount } else { 0 });
+                self.record_sent(&sig);
+                self.sigs.push(sig);
                 {
                     let mut stats = self.stats.lock().unwrap();
                     stats.sent_sigs += 1;
@@ -256,45 +340,60 @@ impl Combo {
             }
             Err(err) => {
                 {
-                    let mut stats = self.stats.lock().unwrap();
+                    let mut stats = stats.lock().unwrap();
                     stats.sent_sigs += 1;
-                    stats.notifications.push(format!("[Combo::build_send] {:?}", err));
+                    stats.failed_sigs += 1;
+                    self.release(&mut stats);
+                    notify!(stats, Level::Warn, "[Combo::build_send] {:?}", err);
                 }
             }
         }
         Ok(())
     }
 
-    pub async fn confirm(&mut self) -> Option<Vec<Miner>> {
+    /// Returns the signature of the landed transaction, if any
+    pub async fn confirm(&mut self) -> Option<Landed> {
         // check confirmations by iterating the sigs, throwing out sigs that expired
         let client = RpcClient::new_with_commitment(RPC.to_string(), CommitmentConfig::confirmed());
         let mut i: usize = 0;
 
+        let before = self.sigs.len();
         self.sigs.retain(|a| {!a.expired()});
+        let expired = before - self.sigs.len();
+        if expired > 0 {
+            let mut stats = self.stats.lock().unwrap();
+            stats.failed_sigs += expired as u64;
+        }
         match client.get_signature_statuses(&self.sigs.iter().map(|x| {x.sig}).collect::<Vec<Signature>>()).await {
             Ok(sig_statuses) => {
-                if sig_statuses.value.len() == 0 {
+                if sig_statuses.value.is_empty() {
                     {
                         let mut stats = self.stats.lock().unwrap();
-                        stats.notifications.push(format!("[Combo::confirm] Empty Result"));
+                        notify!(stats, Level::Debug, "[Combo::confirm] Empty Result");
                     }
                     return None;
                 }
                 for sig_status in sig_statuses.value {
                     if let Some(ss) = sig_status.as_ref() {
-                        if ss.confirmation_status.is_some() {
-                            let cc = ss.confirmation_status.as_ref().unwrap();
+                        if let Some(cc) = ss.confirmation_status.as_ref() {
                             match cc {
                                 TransactionConfirmationStatus::Processed => {}
                                 TransactionConfirmationStatus::Confirmed |
                                     TransactionConfirmationStatus::Finalized => {
+                                        if let Some(err) = ss.err.as_ref() {
+                                            let mut stats = self.stats.lock().unwrap();
+                                            stats.failed_sigs += 1;
+                                            notify!(stats, Level::Error, "[Combo::confirm] Failed {} {:?} https://solana.fm/tx/{}", self.sigs[i].sig, err, self.sigs[i].sig);
+                                            return Some(Landed::Failed(self.sigs[i].sig));
+                                        }
                                         {
                                             let mut stats = self.stats.lock().unwrap();
                                             stats.hashes_submitted += self.miners.len() as u64;
                                             stats.lifetime_hashes += self.miners.len() as u64;
-                                            stats.notifications.push(format!("[Combo::confirm] Confirmed {} https://solana.fm/tx/{}", self.sigs[i].sig, self.sigs[i].sig));
+                                            stats.landed_combos += 1;
+                                            notify!(stats, Level::Info, "[Combo::confirm] Confirmed {} https://solana.fm/tx/{}", self.sigs[i].sig, self.sigs[i].sig);
                                         }
-                                        return Some(self.miners.clone())
+                                        return Some(Landed::Ok(self.sigs[i].sig))
                                     }
                             }
 
@@ -308,7 +407,7 @@ impl Combo {
                 {
                     //TODO:
                     let mut stats = self.stats.lock().unwrap();
-                    stats.notifications.push(format!("[Combo::confirm] {:?}", e));
+                    notify!(stats, Level::Warn, "[Combo::confirm] {:?}", e);
                 }
             }
         }
@@ -316,39 +415,62 @@ impl Combo {
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct Pending {
-    miner: Miner,
-    sigs: Vec<SigTime>,
-    retries: usize,
-    rpc: String,
-    spam: usize,
-    stats: Arc<Mutex<Stats>>,
+
+/// The new proof of a miner whose combo landed. Retries until rpc answers, the miner can't be
+/// mined again without it.
+async fn landed_proof(rpc: &str, stats: &Mutex<Stats>, id: usize, miner: &Pubkey) -> Proof {
+    loop {
+        match get_proof(rpc.to_string(), *miner).await {
+            Ok(proof) => return proof,
+            Err(err) => {
+                let mut stats = stats.lock().unwrap();
+                notify!(stats, Level::Warn, "[Submitter {}] can't read the proof of {}: {}", id, miner, err);
+            }
+        }
+        std::thread::sleep(Duration::from_millis(2000));
+    }
 }
 
+/// Revalidate `miners` against `difficulty`, keeping the valid ones and returning the rest
+fn split_invalid(miners: &mut Vec<Miner>, difficulty: &Hash) -> Vec<Miner> {
+    let mut invalid = vec![];
+    for mut miner in std::mem::take(miners) {
+        if miner.revalidate(difficulty) {
+            miners.push(miner);
+        } else {
+            invalid.push(miner);
+        }
+    }
+    invalid
+}
 
 /// SubmitterThread
 pub struct SubmitterThread {
     pub id: usize,
     /// maximum number of miners to submit for simultaniously
     pub batchsize: usize,
-    /// number of retries
-    pub retries: u32,
-    /// prio fee
-    pub priority_fee: u64,
-    /// the miners currently submitting for
+    /// the miners currently submitting for, all of one authority
     pub miners: Vec<Miner>,
+    /// size of the current batch once complete: batchsize, or fewer for an authority with fewer miners
+    pub target: usize,
+    /// miners received but not yet batched, per authority
+    pub waiting: HashMap<Pubkey, Vec<Miner>>,
     /// the queue from which to pull the next miner
     pub lq: Receiver<Miner>,
     /// the queue to which to push miners
     pub mq: Sender<Miner>,
     pub rpc: String,
     pub stats: Arc<Mutex<Stats>>,
+    pub store: Arc<Mutex<Store>>,
     pub combo: Option<Combo>,
+    /// when this thread last sent a transaction, for ProfitPolicy::Throttle
+    pub last_send: Option<Instant>,
+    /// the difficulty held solutions were last checked against
+    pub difficulty: Option<Hash>,
 }
 
 impl SubmitterThread {
-    pub fn new(id: usize, batchsize: usize, retries: u32, priority_fee: u64, sender: Sender<Miner>, receiver: Receiver<Miner>, rpc: String, stats: Arc<Mutex<Stats>>) -> Self {
+    pub fn new(id: usize, batchsize: usize, sender: Sender<Miner>, receiver: Receiver<Miner>, rpc: String, stats: Arc<Mutex<Stats>>, store: Arc<Mutex<Store>>) -> Self {
         {
             let mut stats = stats.lock().unwrap();
             stats.threads.push(
@@ -358,29 +480,35 @@ impl SubmitterThread {
                     activity: Activity::Idle
                 });
         }
-        SubmitterThread { id, batchsize, retries, priority_fee, miners: vec![], lq: receiver, mq: sender , rpc, stats, combo: None}
+        SubmitterThread { id, batchsize, miners: vec![], target: batchsize, lq: receiver, mq: sender , rpc, stats, store, combo: None, last_send: None, waiting: HashMap::new(), difficulty: None}
     }
 
 
     pub async fn start(&mut self) {
         loop {
+            let paused;
             {
                 let mut stats = self.stats.lock().unwrap();
-                if self.miners.len() == 0 {
+                paused = stats.paused || self.hold_unprofitable(&mut stats) || self.over_budget(&mut stats);
+                let waiting = self.waiting.values().map(Vec::len).sum::<usize>();
+                if self.miners.is_empty() && waiting == 0 {
                     // stats.notifications.push(format!("Empty SubmitterThread."));
                     stats.threads[self.id].activity = Activity::Idle;
-                } else if self.miners.len() == self.batchsize {
+                } else if self.miners.len() >= self.target && paused {
+                    stats.threads[self.id].activity = Activity::Paused(self.miners.len());
+                } else if self.miners.len() >= self.target {
                     stats.threads[self.id].activity = Activity::Sending(self.miners.len());
                 } else {
-                    stats.threads[self.id].activity = Activity::Accumulating(self.miners.len());
+                    stats.threads[self.id].activity = Activity::Accumulating(waiting + self.miners.len());
                 }
             }
 
-            if self.miners.len() < self.batchsize {
+            if self.miners.len() < self.target {
                 let message = self.lq.try_recv();
                 match message {
                     Ok(miner) => {
-                        self.miners.push(miner);
+                        // a transaction is signed by one authority, so batch miners per authority
+                        self.waiting.entry(miner.authority).or_default().push(miner);
                     },
                     Err(TryRecvError::Empty) => { 
                         std::thread::sleep(Duration::from_millis(5000));
@@ -389,77 +517,246 @@ impl SubmitterThread {
                         panic!()
                     },
                 };
+                self.fill_batch();
                 continue;
             }
             
             // we have now a full batch.
 
+            // held solutions are checked before a combo is built or resent
+            let difficulty_changed = latest_difficulty(&self.stats).is_some_and(|d| self.difficulty != Some(d));
+            let mut revalidate = false;
+
             // if combo exists, check confirmations
             match self.combo {
-                Some(ref mut combo) => {
-                    if let Some(_) = combo.confirm().await {
+                Some(ref mut combo) => match combo.confirm().await {
+                    Some(Landed::Ok(sig)) => {
                         {
-                            // reduce tip
                             let mut stats = self.stats.lock().unwrap();
-                            stats.tip = stats.tip / 2;
+                            let ctx = stats.tip_context(combo.miners.len());
+                            let tip = stats.tip_strategy.landed(&ctx);
+                            stats.tip = tip;
                         }
-                        self.combo = None;
                         let mut rewards_added: u64 = 0;
                         while let Some(mut m) = self.miners.pop() {
                             std::thread::sleep(Duration::from_millis(500));
-                            let state = get_proof(self.rpc.clone(), m.pubkey).await;
+                            let state = landed_proof(&self.rpc, &self.stats, self.id, &m.pubkey).await;
                             m.total_hashes = state.total_hashes;
                             rewards_added += state.total_rewards - m.total_rewards;
                             m.total_rewards = state.total_rewards;
                             let new_hash = state.hash;
 
+                            self.store.lock().unwrap().record_landed(&m, &sig, new_hash.into());
                             m.state = MinerState::Minable(new_hash.into());
+                            m.challenge = new_hash.into();
+                            self.stats.lock().unwrap().miner_landed(&m);
                             self.mq.send(m).unwrap();
                         }
+                        let meta = get_transaction_meta(self.rpc.clone(), &sig).await;
+                        let costs = combo.costs(&sig, meta.as_ref());
+                        combo.record_confirmed(&sig, meta.as_ref(), &costs, rewards_added);
                         {
                             let mut stats = self.stats.lock().unwrap();
+                            combo.release(&mut stats);
                             stats.ore_mined += rewards_added;
                             stats.lifetime_rewards += rewards_added;
+                            stats.add_costs(&combo.authority.pubkey(), &costs);
                         }
-                    } else {
+                        self.combo = None;
+                        self.fill_batch();
+                        let mut store = self.store.lock().unwrap();
+                        store.record_budgets(&self.stats.lock().unwrap());
+                        store.flush(&self.stats);
+                    }
+                    Some(Landed::Failed(sig)) => {
+                        // the fee is gone; build a new combo from solutions that still hold
+                        let meta = get_transaction_meta(self.rpc.clone(), &sig).await;
+                        let costs = combo.failed_costs(meta.as_ref());
+                        combo.record_failed(&sig, meta.as_ref(), &costs);
+                        {
+                            let mut stats = self.stats.lock().unwrap();
+                            combo.release(&mut stats);
+                            stats.add_costs(&combo.authority.pubkey(), &costs);
+                        }
+                        self.combo = None;
+                        revalidate = true;
+                        let mut store = self.store.lock().unwrap();
+                        store.record_budgets(&self.stats.lock().unwrap());
+                        store.flush(&self.stats);
+                    }
+                    None if difficulty_changed => revalidate = true,
+                    None if paused => {
+                        if combo.sigs.is_empty() {
+                            combo.release(&mut self.stats.lock().unwrap());
+                        }
+                    }
+                    None => {
                         {
                             // Combo didn't land; adjust tip
                             let mut stats = self.stats.lock().unwrap();
-                            if stats.tip > 500_000 {
-                                stats.tip = TIP;
-                            } else {
-                                if (stats.rate as f64/ ORE_DECIMALS as f64) * stats.price < (185.0 * stats.tip as f64 / LAMPORTS_PER_SOL as f64) {
-                                    stats.tip = stats.tip / 2;
-                                } else {
-                                    stats.tip = stats.tip * 101 / 100;
-                                }
-                            }
-                            combo.adjust_tip();
+                            let ctx = stats.tip_context(combo.miners.len());
+                            let tip = stats.tip_strategy.pending(&ctx);
+                            stats.tip = tip;
                         }
-                        combo.build_send_with_alt().await;
+                        combo.adjust_tip();
+                        combo.build_send_with_alt().await.ok();
+                        self.last_send = Some(Instant::now());
                     }
-
                 },
-                None => {
+                None if difficulty_changed => revalidate = true,
+                None if paused => {},
+                None => match get_treasury(self.rpc.clone()).await {
                     // create new combo
-                    let treasury = get_treasury(self.rpc.clone()).await;
-                    let bus = self.find_bus_id(treasury.reward_rate).await;
-                    let mut combo = Combo::new(self.miners.clone(), self.stats.clone(), bus).await;
-                    combo.build_send_with_alt().await;
-                    self.combo = Some(combo);
-
+                    Ok(treasury) => {
+                        let bus = self.find_bus_id(treasury.reward_rate).await;
+                        let mut combo = Combo::new(self.miners.clone(), self.stats.clone(), bus).await;
+                        combo.build_send_with_alt().await.ok();
+                        self.combo = Some(combo);
+                        self.last_send = Some(Instant::now());
+                    }
+                    Err(err) => {
+                        let mut stats = self.stats.lock().unwrap();
+                        notify!(stats, Level::Warn, "[Submitter {}] can't read the treasury: {}", self.id, err);
+                    }
                 }
             }
+            if revalidate {
+                self.follow_difficulty();
+            }
             std::thread::sleep(Duration::from_millis(2000));
         }
     }
 
+    /// Check every held solution against the latest difficulty: waiting miners, the batch and
+    /// its combo. Invalid ones go back to be mined. A combo that lost a miner is dropped, since
+    /// its transaction can't land anymore, and the rest of its batch is topped up for a new one.
+    fn follow_difficulty(&mut self) {
+        let Some(difficulty) = latest_difficulty(&self.stats) else {
+            return;
+        };
+        self.difficulty = Some(difficulty);
+        let mut invalid = split_invalid(&mut self.miners, &difficulty);
+        if !invalid.is_empty() {
+            if let Some(mut combo) = self.combo.take() {
+                combo.release(&mut self.stats.lock().unwrap());
+            }
+        }
+        for batch in self.waiting.values_mut() {
+            invalid.extend(split_invalid(batch, &difficulty));
+        }
+        if invalid.is_empty() {
+            return;
+        }
+        {
+            let mut store = self.store.lock().unwrap();
+            for miner in &invalid {
+                store.record_solution(miner);
+            }
+        }
+        {
+            let mut stats = self.stats.lock().unwrap();
+            for miner in &invalid {
+                stats.update_miner(miner);
+            }
+            notify!(stats, Level::Info, "[Submitter {}] Sent {} miners back to mining, their solutions miss the new difficulty", self.id, invalid.len());
+        }
+        for miner in invalid {
+            self.mq.send(miner).unwrap();
+        }
+        self.fill_batch();
+    }
+
+
+    /// Batch size for `authority`: one with fewer miners than a batch still gets to send
+    fn target_for(&self, authority: &Pubkey) -> usize {
+        self.batchsize.min(authority::find(authority).miners as usize)
+    }
+
+    /// Move waiting miners into the batch, up to its target. An empty batch is started with the
+    /// first authority that has enough miners waiting. Never touches the batch of a combo in flight.
+    fn fill_batch(&mut self) {
+        if self.combo.is_some() {
+            return;
+        }
+        let authority = match self.miners.first() {
+            Some(miner) => miner.authority,
+            None => match self.waiting.iter().find(|(a, batch)| batch.len() >= self.target_for(a)) {
+                Some((authority, _)) => *authority,
+                None => return,
+            },
+        };
+        self.target = self.target_for(&authority);
+        if let Some(batch) = self.waiting.get_mut(&authority) {
+            let missing = self.target.saturating_sub(self.miners.len()).min(batch.len());
+            self.miners.extend(batch.drain(..missing));
+        }
+    }
+
+    /// Whether to hold a full batch because sending it is expected to lose money, per PROFIT_POLICY
+    fn hold_unprofitable(&self, stats: &mut Stats) -> bool {
+        let estimate = Profitability::estimate(stats, self.target);
+        let unprofitable = estimate.is_some_and(|p| !p.profitable());
+        if unprofitable != stats.unprofitable {
+            stats.unprofitable = unprofitable;
+            match estimate {
+                Some(p) if unprofitable => notify!(stats, Level::Warn,
+                    "[Submitter {}] Unprofitable: expected ${:.4} per tx (reward ${:.4}, cost ${:.4}, landing {:.0}%)",
+                    self.id, p.expected, p.reward, p.cost, p.landing * 100.0),
+                _ => notify!(stats, Level::Info, "[Submitter {}] Profitable again", self.id),
+            }
+        }
+        match PROFIT_POLICY {
+            ProfitPolicy::Ignore => false,
+            ProfitPolicy::Pause => unprofitable,
+            ProfitPolicy::Throttle(secs) => unprofitable
+                && self.last_send.is_some_and(|t| t.elapsed() < Duration::from_secs(secs)),
+        }
+    }
+
+    /// Whether sending the current batch could exceed BUDGET_HOURLY, BUDGET_DAILY or the
+    /// budgets of its authority
+    fn over_budget(&self, stats: &mut Stats) -> bool {
+        if self.miners.len() < self.target {
+            return false;
+        }
+        let authority = authority::find(&self.miners[0].authority);
+        let key = authority.pubkey().to_string();
+        // a combo in flight already holds its reservation
+        let reserved = self.combo.as_ref().map_or(0, |combo| combo.reserved);
+        let max_cost = budget::max_cost(&self.miners, stats);
+        let cost = max_cost.saturating_sub(reserved);
+        let now = chrono::Utc::now().timestamp();
+        let global = stats.budget.exceeded_until(cost, now, BUDGET_HOURLY, BUDGET_DAILY);
+        let own = stats.authority_budgets.get(&key).copied().unwrap_or_default()
+            .exceeded_until(cost, now, authority.budget_hourly, authority.budget_daily);
+        match global.max(own) {
+            Some(until) => {
+                if stats.over_budget.insert(key.clone(), until) != Some(until) {
+                    let until = chrono::DateTime::from_timestamp(until, 0).map(|t| t.to_rfc3339()).unwrap_or_default();
+                    if Budget::exceeds_limit(max_cost, BUDGET_HOURLY, BUDGET_DAILY)
+                        || Budget::exceeds_limit(max_cost, authority.budget_hourly, authority.budget_daily) {
+                        notify!(stats, Level::Error, "[Submitter {}] A batch of {} miners of {} can cost {} lamports, more than its budget limits allow; it will never be sent. Lower MINERLIMIT or raise the limits",
+                            self.id, self.miners.len(), key, max_cost);
+                    } else {
+                        notify!(stats, Level::Warn, "[Submitter {}] Budget of {} exhausted, idling until {}", self.id, key, until);
+                    }
+                }
+                true
+            }
+            None => {
+                if stats.over_budget.remove(&key).is_some() {
+                    notify!(stats, Level::Info, "[Submitter {}] Budget of {} available again", self.id, key);
+                }
+                false
+            }
+        }
+    }
 
     async fn find_bus_id(&self, reward_rate: u64) -> Bus {
-        let mut rng = rand::thread_rng();
+        let mut rng = rand::rng();
         loop {
             
-            let bus_id = rng.gen_range(0..8);
+            let bus_id = rng.random_range(0..8);
             if let Ok(bus) = self.get_bus(bus_id).await {
                 if bus.rewards.gt(&reward_rate.saturating_mul(self.batchsize as u64 * 4)) {
                     return bus;
@@ -487,7 +784,9 @@ impl SubmitterThread {
         let client =
             RpcClient::new_with_commitment(self.rpc.clone(), CommitmentConfig::confirmed());
         let data = client.get_account_data(&BUS_ADDRESSES[id]).await?;
-        Ok(*Bus::try_from_bytes(&data).unwrap())
+        Bus::try_from_bytes(&data)
+            .copied()
+            .map_err(|err| custom_error(format!("can't parse bus {}: {:?}", id, err)))
     }
 
 }
@@ -0,0 +1,370 @@
+use std::fmt::Debug;
+
+use serde::{Deserialize, Serialize};
+use solana_program::native_token::LAMPORTS_PER_SOL;
+
+use crate::config::{MAX_TIP, MIN_TIP, ORE_DECIMALS, TIP};
+
+/// Recent landed jito tips, in lamports
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct TipFloor {
+    pub p25: u64,
+    pub p50: u64,
+    pub p75: u64,
+    pub p95: u64,
+    pub p99: u64,
+}
+
+#[derive(Deserialize)]
+struct TipFloorResponse {
+    landed_tips_25th_percentile: f64,
+    landed_tips_50th_percentile: f64,
+    landed_tips_75th_percentile: f64,
+    landed_tips_95th_percentile: f64,
+    landed_tips_99th_percentile: f64,
+}
+
+impl TipFloor {
+    /// Tip at `percentile`, interpolated between the published ones
+    pub fn percentile(&self, percentile: f64) -> u64 {
+        let points = [(0.0, 0), (25.0, self.p25), (50.0, self.p50), (75.0, self.p75), (95.0, self.p95), (99.0, self.p99)];
+        let percentile = percentile.clamp(0.0, 99.0);
+        let i = points.iter().position(|(p, _)| *p >= percentile).unwrap_or(points.len() - 1).max(1);
+        let ((p0, t0), (p1, t1)) = (points[i - 1], points[i]);
+        let share = (percentile - p0) / (p1 - p0);
+        (t0 as f64 + share * (t1 as f64 - t0 as f64)) as u64
+    }
+}
+
+/// Fetch the recent landed tip percentiles from jito
+pub async fn get_tip_floor(client: &reqwest::Client) -> Option<TipFloor> {
+    let res = client.get("https://bundles.jito.wtf/api/v1/bundles/tip_floor").send().await.ok()?;
+    let data = res.json::<Vec<TipFloorResponse>>().await.ok()?;
+    let data = data.first()?;
+    let lamports = |sol: f64| (sol * LAMPORTS_PER_SOL as f64) as u64;
+    Some(TipFloor {
+        p25: lamports(data.landed_tips_25th_percentile),
+        p50: lamports(data.landed_tips_50th_percentile),
+        p75: lamports(data.landed_tips_75th_percentile),
+        p95: lamports(data.landed_tips_95th_percentile),
+        p99: lamports(data.landed_tips_99th_percentile),
+    })
+}
+
+/// What a tip strategy gets to look at
+#[derive(Debug, Clone, Copy)]
+pub struct TipContext {
+    /// current tip in lamports
+    pub tip: u64,
+    /// number of miners in the transaction
+    pub miners: usize,
+    /// ore reward rate per hash
+    pub rate: u64,
+    pub ore_price: f64,
+    pub sol_price: f64,
+    pub tip_floor: Option<TipFloor>,
+}
+
+impl TipContext {
+    /// Value of the ore a transaction earns if it lands, in lamports. None without prices.
+    pub fn reward_lamports(&self) -> Option<u64> {
+        if self.ore_price <= 0.0 || self.sol_price <= 0.0 {
+            return None;
+        }
+        let ore = self.miners as f64 * self.rate as f64 / ORE_DECIMALS as f64;
+        Some((ore * self.ore_price / self.sol_price * LAMPORTS_PER_SOL as f64) as u64)
+    }
+}
+
+/// Decides the jito tip. Called by submitters after a combo landed and every time one
+/// is resent because it hasn't landed yet; returns the new tip in lamports.
+pub trait TipStrategy: Debug + Send {
+    fn landed(&mut self, ctx: &TipContext) -> u64;
+    fn pending(&mut self, ctx: &TipContext) -> u64;
+}
+
+/// Tip strategies, configured in TIP_STRATEGY
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub enum TipStrategyKind {
+    /// halve after landing, +1% while pending, halve while the tip is worth more than one
+    /// miner's reward, reset to TIP above MAX_TIP, never below MIN_TIP
+    Heuristic,
+    /// always tip this many lamports
+    Fixed(u64),
+    /// the given percentile of recently landed jito tips
+    JitoPercentile(f64),
+    /// steer the tip towards a target landing rate
+    Pid { target: f64, kp: f64, ki: f64, kd: f64 },
+    /// another strategy, capped at this share of the expected reward
+    EvCapped(&'static TipStrategyKind, f64),
+}
+
+impl TipStrategyKind {
+    pub fn strategy(&self) -> Box<dyn TipStrategy> {
+        match *self {
+            TipStrategyKind::Heuristic => Box::new(Heuristic),
+            TipStrategyKind::Fixed(tip) => Box::new(Fixed(tip)),
+            TipStrategyKind::JitoPercentile(percentile) => Box::new(JitoPercentile(percentile)),
+            TipStrategyKind::Pid { target, kp, ki, kd } => Box::new(Pid::new(target, kp, ki, kd)),
+            TipStrategyKind::EvCapped(inner, share) => Box::new(EvCapped { inner: inner.strategy(), share }),
+        }
+    }
+}
+
+/// The original tip heuristic
+#[derive(Debug)]
+pub struct Heuristic;
+
+impl TipStrategy for Heuristic {
+    fn landed(&mut self, ctx: &TipContext) -> u64 {
+        (ctx.tip / 2).max(MIN_TIP)
+    }
+
+    fn pending(&mut self, ctx: &TipContext) -> u64 {
+        if ctx.tip > MAX_TIP {
+            return TIP;
+        }
+        let reward = (ctx.rate as f64 / ORE_DECIMALS as f64) * ctx.ore_price;
+        let cost = ctx.sol_price * ctx.tip as f64 / LAMPORTS_PER_SOL as f64;
+        let tip = if reward < cost {
+            ctx.tip / 2
+        } else {
+            ctx.tip * 101 / 100
+        };
+        tip.max(MIN_TIP)
+    }
+}
+
+#[derive(Debug)]
+pub struct Fixed(pub u64);
+
+impl TipStrategy for Fixed {
+    fn landed(&mut self, _ctx: &TipContext) -> u64 {
+        self.0
+    }
+
+    fn pending(&mut self, _ctx: &TipContext) -> u64 {
+        self.0
+    }
+}
+
+/// Follows the jito tip floor. Keeps the current tip while the floor is unknown.
+#[derive(Debug)]
+pub struct JitoPercentile(pub f64);
+
+impl JitoPercentile {
+    fn tip(&self, ctx: &TipContext) -> u64 {
+        ctx.tip_floor.map_or(ctx.tip, |floor| floor.percentile(self.0).clamp(MIN_TIP, MAX_TIP))
+    }
+}
+
+impl TipStrategy for JitoPercentile {
+    fn landed(&mut self, ctx: &TipContext) -> u64 {
+        self.tip(ctx)
+    }
+
+    fn pending(&mut self, ctx: &TipContext) -> u64 {
+        self.tip(ctx)
+    }
+}
+
+/// PID controller on the landing rate. Every landed combo counts as a 1 and every resend
+/// as a 0 in an exponential moving average; the tip is scaled by the controller output.
+#[derive(Debug)]
+pub struct Pid {
+    target: f64,
+    kp: f64,
+    ki: f64,
+    kd: f64,
+    /// smoothed landing rate
+    rate: f64,
+    integral: f64,
+    last_error: f64,
+}
+
+impl Pid {
+    /// weight of a new sample in the landing rate average
+    const SMOOTHING: f64 = 0.1;
+    /// bounds of the integral term, against windup
+    const INTEGRAL_LIMIT: f64 = 10.0;
+
+    pub fn new(target: f64, kp: f64, ki: f64, kd: f64) -> Self {
+        Pid { target, kp, ki, kd, rate: target, integral: 0.0, last_error: 0.0 }
+    }
+
+    fn update(&mut self, ctx: &TipContext, sample: f64) -> u64 {
+        self.rate += Self::SMOOTHING * (sample - self.rate);
+        let error = self.target - self.rate;
+        self.integral = (self.integral + error).clamp(-Self::INTEGRAL_LIMIT, Self::INTEGRAL_LIMIT);
+        let output = self.kp * error + self.ki * self.integral + self.kd * (error - self.last_error);
+        self.last_error = error;
+        ((ctx.tip.max(MIN_TIP) as f64 * (1.0 + output).max(0.0)) as u64).clamp(MIN_TIP, MAX_TIP)
+    }
+}
+
+impl TipStrategy for Pid {
+    fn landed(&mut self, ctx: &TipContext) -> u64 {
+        self.update(ctx, 1.0)
+    }
+
+    fn pending(&mut self, ctx: &TipContext) -> u64 {
+        self.update(ctx, 0.0)
+    }
+}
+
+/// Never tip more than `share` of what the transaction earns
+#[derive(Debug)]
+pub struct EvCapped {
+    inner: Box<dyn TipStrategy>,
+    share: f64,
+}
+
+impl EvCapped {
+    fn cap(&self, ctx: &TipContext, tip: u64) -> u64 {
+        match ctx.reward_lamports() {
+            Some(reward) => tip.min((reward as f64 * self.share) as u64),
+            None => tip,
+        }
+    }
+}
+
+impl TipStrategy for EvCapped {
+    fn landed(&mut self, ctx: &TipContext) -> u64 {
+        let tip = self.inner.landed(ctx);
+        self.cap(ctx, tip)
+    }
+
+    fn pending(&mut self, ctx: &TipContext) -> u64 {
+        let tip = self.inner.pending(ctx);
+        self.cap(ctx, tip)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx(tip: u64) -> TipContext {
+        TipContext { tip, miners: 20, rate: 0, ore_price: 0.0, sol_price: 0.0, tip_floor: None }
+    }
+
+    fn floor() -> TipFloor {
+        TipFloor { p25: 100, p50: 200, p75: 300, p95: 500, p99: 900 }
+    }
+
+    #[test]
+    fn percentile_interpolates_between_published_points() {
+        let floor = floor();
+        assert_eq!(floor.percentile(0.0), 0);
+        assert_eq!(floor.percentile(12.5), 50);
+        assert_eq!(floor.percentile(25.0), 100);
+        assert_eq!(floor.percentile(37.5), 150);
+        assert_eq!(floor.percentile(50.0), 200);
+        assert_eq!(floor.percentile(97.0), 700);
+        assert_eq!(floor.percentile(99.0), 900);
+        // outside the published range
+        assert_eq!(floor.percentile(-5.0), 0);
+        assert_eq!(floor.percentile(100.0), 900);
+    }
+
+    #[test]
+    fn heuristic_halves_after_landing_and_grows_while_pending() {
+        let mut strategy = Heuristic;
+        assert_eq!(strategy.landed(&ctx(40_000)), 20_000);
+        assert_eq!(strategy.pending(&ctx(40_000)), 40_400);
+    }
+
+    #[test]
+    fn heuristic_halves_while_the_tip_costs_more_than_a_reward() {
+        let mut strategy = Heuristic;
+        // one miner earns $0.0001, the tip costs $0.04
+        let ctx = TipContext { rate: ORE_DECIMALS / 10_000, ore_price: 1.0, sol_price: 100.0, ..ctx(400_000) };
+        assert_eq!(strategy.pending(&ctx), 200_000);
+    }
+
+    #[test]
+    fn heuristic_resets_above_max_tip() {
+        assert_eq!(Heuristic.pending(&ctx(MAX_TIP + 1)), TIP);
+    }
+
+    #[test]
+    fn heuristic_never_drops_below_min_tip() {
+        let mut strategy = Heuristic;
+        let mut tip = TIP;
+        for _ in 0..64 {
+            tip = strategy.landed(&ctx(tip));
+        }
+        assert_eq!(tip, MIN_TIP);
+        // and recovers from there while pending
+        assert!(strategy.pending(&ctx(tip)) > MIN_TIP);
+        assert_eq!(strategy.landed(&ctx(0)), MIN_TIP);
+        assert_eq!(strategy.pending(&ctx(0)), MIN_TIP);
+    }
+
+    #[test]
+    fn fixed_ignores_the_context() {
+        let mut strategy = Fixed(12_345);
+        assert_eq!(strategy.landed(&ctx(1)), 12_345);
+        assert_eq!(strategy.pending(&ctx(MAX_TIP)), 12_345);
+    }
+
+    #[test]
+    fn jito_percentile_follows_the_floor_within_bounds() {
+        let mut strategy = JitoPercentile(50.0);
+        assert_eq!(strategy.landed(&ctx(7_000)), 7_000, "keeps the tip without a floor");
+        let mid = TipFloor { p25: 2_000, p50: 4_000, p75: 6_000, p95: 8_000, p99: 10_000 };
+        assert_eq!(strategy.pending(&TipContext { tip_floor: Some(mid), ..ctx(7_000) }), 4_000);
+        // the floor below MIN_TIP
+        assert_eq!(strategy.pending(&TipContext { tip_floor: Some(floor()), ..ctx(7_000) }), MIN_TIP);
+        let high = TipFloor { p50: MAX_TIP * 2, ..mid };
+        assert_eq!(strategy.pending(&TipContext { tip_floor: Some(high), ..ctx(7_000) }), MAX_TIP);
+    }
+
+    #[test]
+    fn pid_raises_the_tip_while_missing_the_target_and_lowers_it_above() {
+        let mut strategy = Pid::new(0.5, 0.5, 0.05, 0.0);
+        let mut tip = 10_000;
+        for _ in 0..5 {
+            let next = strategy.pending(&ctx(tip));
+            assert!(next > tip, "{} should rise above {}", next, tip);
+            tip = next;
+        }
+        let mut strategy = Pid::new(0.5, 0.5, 0.05, 0.0);
+        let mut tip = 10_000;
+        for _ in 0..5 {
+            let next = strategy.landed(&ctx(tip));
+            assert!(next < tip, "{} should drop below {}", next, tip);
+            tip = next;
+        }
+    }
+
+    #[test]
+    fn pid_stays_within_bounds() {
+        let mut strategy = Pid::new(0.9, 5.0, 1.0, 1.0);
+        let mut tip = MAX_TIP;
+        for _ in 0..100 {
+            tip = strategy.pending(&ctx(tip));
+            assert!((MIN_TIP..=MAX_TIP).contains(&tip));
+        }
+        assert_eq!(tip, MAX_TIP);
+        // long enough to unwind the integral
+        for _ in 0..300 {
+            tip = strategy.landed(&ctx(tip));
+            assert!((MIN_TIP..=MAX_TIP).contains(&tip));
+        }
+        assert_eq!(tip, MIN_TIP);
+    }
+
+    #[test]
+    fn ev_capped_caps_at_a_share_of_the_reward() {
+        let mut strategy = EvCapped { inner: Box::new(Fixed(300_000)), share: 0.1 };
+        assert_eq!(strategy.landed(&ctx(0)), 300_000, "no cap without prices");
+        // 20 miners earning 0.0001 SOL each: 2_000_000 lamports, capped at 10%
+        let priced = TipContext { rate: ORE_DECIMALS / 10_000, ore_price: 50.0, sol_price: 50.0, ..ctx(0) };
+        assert_eq!(priced.reward_lamports(), Some(2_000_000));
+        assert_eq!(strategy.pending(&priced), 200_000);
+        let mut strategy = EvCapped { inner: Box::new(Fixed(100_000)), share: 0.1 };
+        assert_eq!(strategy.pending(&priced), 100_000, "below the cap");
+    }
+}
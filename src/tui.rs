@@ -0,0 +1,339 @@
+use std::{
+    io::{self, stdout},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use log::Level;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Cell, List, ListItem, ListState, Paragraph, Row, Table, TableState},
+};
+use solana_program::native_token::LAMPORTS_PER_SOL;
+
+use crate::{claim::spawn_claim_all, config::{BUDGET_DAILY, BUDGET_HOURLY, MAX_TIP, MINERLIMIT, MIN_TIP, ORE_DECIMALS}, ogre::Ogre, profit::Profitability, stats::Stats};
+
+/// Tip change per keypress, in percent
+const TIP_STEP: u64 = 10;
+
+#[derive(PartialEq)]
+enum Focus {
+    Miners,
+    Log,
+}
+
+struct App {
+    start: Instant,
+    focus: Focus,
+    miners: TableState,
+    log: ListState,
+    filter: String,
+    editing_filter: bool,
+    /// least severe notification level shown
+    level: Level,
+}
+
+/// Run the dashboard until the user quits. Blocks the calling thread.
+pub fn run(ogre: Arc<Ogre>) -> io::Result<()> {
+    enable_raw_mode()?;
+    execute!(stdout(), EnterAlternateScreen)?;
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        disable_raw_mode().ok();
+        execute!(stdout(), LeaveAlternateScreen).ok();
+        hook(info);
+    }));
+
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+    let mut app = App {
+        start: Instant::now(),
+        focus: Focus::Miners,
+        miners: TableState::default().with_selected(Some(0)),
+        log: ListState::default(),
+        filter: String::new(),
+        editing_filter: false,
+        level: Level::Trace,
+    };
+    let result = event_loop(&mut terminal, &mut app, &ogre);
+
+    disable_raw_mode()?;
+    execute!(stdout(), LeaveAlternateScreen)?;
+    result
+}
+
+fn event_loop(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App, ogre: &Ogre) -> io::Result<()> {
+    loop {
+        terminal.draw(|frame| {
+            let stats = ogre.stats.lock().unwrap();
+            draw(frame, app, &stats, ogre);
+        })?;
+
+        if !event::poll(Duration::from_millis(250))? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        if app.editing_filter {
+            match key.code {
+                KeyCode::Enter => app.editing_filter = false,
+                KeyCode::Esc => {
+                    app.filter.clear();
+                    app.editing_filter = false;
+                }
+                KeyCode::Backspace => {
+                    app.filter.pop();
+                }
+                KeyCode::Char(c) => app.filter.push(c),
+                _ => {}
+            }
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('q') => return Ok(()),
+            KeyCode::Char('p') => {
+                let mut stats = ogre.stats.lock().unwrap();
+                stats.paused = !stats.paused;
+            }
+            KeyCode::Char('c') => spawn_claim_all(ogre.stats.clone()),
+            KeyCode::Char('+') => {
+                let mut stats = ogre.stats.lock().unwrap();
+                stats.tip = (stats.tip * (100 + TIP_STEP) / 100).clamp(MIN_TIP, MAX_TIP);
+            }
+            KeyCode::Char('-') => {
+                let mut stats = ogre.stats.lock().unwrap();
+                stats.tip = (stats.tip * (100 - TIP_STEP) / 100).clamp(MIN_TIP, MAX_TIP);
+            }
+            KeyCode::Char('/') => app.editing_filter = true,
+            KeyCode::Char('l') => {
+                app.level = match app.level {
+                    Level::Trace => Level::Info,
+                    Level::Info | Level::Debug => Level::Warn,
+                    Level::Warn => Level::Error,
+                    Level::Error => Level::Trace,
+                };
+            }
+            KeyCode::Esc => app.filter.clear(),
+            KeyCode::Tab => {
+                app.focus = if app.focus == Focus::Miners { Focus::Log } else { Focus::Miners };
+            }
+            KeyCode::Up => scroll(app, -1),
+            KeyCode::Down => scroll(app, 1),
+            KeyCode::PageUp => scroll(app, -10),
+            KeyCode::PageDown => scroll(app, 10),
+            _ => {}
+        }
+    }
+}
+
+fn scroll(app: &mut App, delta: isize) {
+    let state = match app.focus {
+        Focus::Miners => app.miners.selected_mut(),
+        Focus::Log => app.log.selected_mut(),
+    };
+    let current = state.unwrap_or(0) as isize;
+    // clamped to the list length when drawing
+    *state = Some((current + delta).max(0) as usize);
+}
+
+fn header<'a>(cells: &[&'a str]) -> Row<'a> {
+    Row::new(cells.iter().map(|c| Cell::from(*c).bold()).collect::<Vec<_>>())
+}
+
+fn color(good: bool) -> Color {
+    if good { Color::Green } else { Color::Red }
+}
+
+fn draw(frame: &mut Frame, app: &mut App, stats: &Stats, ogre: &Ogre) {
+    let elapsed = app.start.elapsed().as_secs_f64().max(1.0);
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(4),
+            Constraint::Length(4),
+            Constraint::Length(6),
+            Constraint::Min(8),
+            Constraint::Length(10),
+            Constraint::Length(1),
+        ])
+        .split(frame.size());
+
+    // market
+    let rate = stats.rate as f64 / ORE_DECIMALS as f64;
+    let expected = Profitability::estimate(stats, MINERLIMIT as usize);
+    let difficulty = stats.difficulty
+        .map(|d| solana_sdk::keccak::Hash::from(d).to_string())
+        .unwrap_or_default();
+    let market = Table::new(vec![Row::new(vec![
+        Cell::from(format!("{:.9} (${:.4})", rate, rate * stats.price)).fg(color(stats.rate > stats.oldrate)),
+        Cell::from(format!("{:.2} (+{:.2})", stats.supply, stats.supply - stats.oldsupply)).fg(color(stats.supply - stats.oldsupply > 1.0)),
+        Cell::from(format!("${:.2}", stats.supply * stats.price)).fg(color(stats.price > stats.oldprice)),
+        Cell::from(format!("${:.2}{}", stats.price, if stats.price_stale { " (stale)" } else { "" })).fg(color(stats.price > stats.oldprice)),
+        Cell::from(difficulty),
+        match expected {
+            Some(p) => Cell::from(format!("${:.4} ({:.0}%)", p.expected, p.landing * 100.0)).fg(color(p.profitable())),
+            None => Cell::from("-"),
+        },
+        Cell::from(format!("{} (${:.4})", stats.tip, stats.sol_price * stats.tip as f64 / LAMPORTS_PER_SOL as f64)),
+        Cell::from(format!("{}", stats.priority_fee)),
+    ])], [Constraint::Ratio(1, 8); 8])
+        .header(header(&["Reward Rate", "Supply", "MCAP", "Price", "Difficulty", "Expected / Tx", "Tip", "Prio Fee"]))
+        .block(Block::default().borders(Borders::ALL).title("Market"));
+    frame.render_widget(market, rows[0]);
+
+    // throughput
+    let throughput = Table::new(vec![Row::new(vec![
+        format!("{:.0}s", elapsed),
+        format!("{}", stats.miners),
+        format!("{}", stats.hashes_submitted),
+        format!("{}", stats.hashes_mined),
+        format!("{:.4}", stats.hashes_submitted as f64 / elapsed),
+        format!("{:.4}", stats.hashes_mined as f64 / elapsed),
+        format!("{} / {} / {}", stats.sent_sigs, stats.landed_combos, stats.failed_sigs),
+        format!("{} / {}", ogre.minable.len(), ogre.loaded.len()),
+        format!("{:.4}", stats.balance as f64 / LAMPORTS_PER_SOL as f64),
+        format!("{:.4}", stats.lamports as f64 / LAMPORTS_PER_SOL as f64),
+    ])], [Constraint::Ratio(1, 10); 10])
+        .header(header(&["Elapsed", "Miners", "H sub", "H mined", "HpS (sub)", "HpS (mine)", "Sent/Landed/Failed", "Minable/Loaded", "Wallet", "Miner Funds"]))
+        .block(Block::default().borders(Borders::ALL).title("Throughput"));
+    frame.render_widget(throughput, rows[1]);
+
+    // profit
+    let ore = stats.ore_mined as f64 / ORE_DECIMALS as f64;
+    let life = stats.lifetime_rewards as f64 / ORE_DECIMALS as f64;
+    let spent = stats.sol_spent as f64 / LAMPORTS_PER_SOL as f64;
+    let net = ore * stats.price - spent * stats.sol_price;
+    let per_hour = 3600.0 / elapsed;
+    let profit = Table::new(vec![
+        Row::new(vec![
+            Cell::from("Gross"),
+            Cell::from(format!("{:.4} (${:.4})", ore, ore * stats.price)),
+            Cell::from(format!("{:.4} (${:.4})", life, life * stats.price)),
+            Cell::from(format!("${:.4}", ore * stats.price * per_hour)),
+            Cell::from(format!("${:.4}", ore * stats.price * per_hour * 24.0)),
+        ]),
+        Row::new(vec![
+            Cell::from("Costs"),
+            Cell::from(format!("{:.4} SOL (${:.4})", spent, spent * stats.sol_price)),
+            Cell::from(format!("fee {:.4} (prio {:.4}) tip {:.4} reg {:.4}",
+                stats.costs.fee as f64 / LAMPORTS_PER_SOL as f64,
+                stats.costs.priority as f64 / LAMPORTS_PER_SOL as f64,
+                stats.costs.tip as f64 / LAMPORTS_PER_SOL as f64,
+                stats.costs.registration as f64 / LAMPORTS_PER_SOL as f64)),
+            Cell::from(format!("${:.4}", spent * stats.sol_price * per_hour)),
+            Cell::from(format!("${:.4}", spent * stats.sol_price * per_hour * 24.0)),
+        ]),
+        Row::new(vec![
+            Cell::from("Net"),
+            Cell::from(format!("${:.4}", net)).fg(color(net > 0.0)),
+            Cell::from(""),
+            Cell::from(format!("${:.4}", net * per_hour)).fg(color(net > 0.0)),
+            Cell::from(format!("${:.4}", net * per_hour * 24.0)).fg(color(net > 0.0)),
+        ]),
+    ], [Constraint::Length(6), Constraint::Ratio(1, 4), Constraint::Ratio(1, 3), Constraint::Ratio(1, 6), Constraint::Ratio(1, 6)])
+        .header(header(&["Profit", "Session", "Life / Breakdown", "USD/Hr", "USD/Day"]))
+        .block(Block::default().borders(Borders::ALL).title("Profit"));
+    frame.render_widget(profit, rows[2]);
+
+    // threads and miners
+    let middle = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(25), Constraint::Percentage(75)])
+        .split(rows[3]);
+    let threads = Table::new(stats.threads.iter().map(|t| Row::new(vec![
+        format!("{:?}", t.ttype),
+        format!("{}", t.id),
+        format!("{:?}", t.activity),
+    ])).collect::<Vec<_>>(), [Constraint::Length(10), Constraint::Length(4), Constraint::Min(10)])
+        .header(header(&["type", "id", "status"]))
+        .block(Block::default().borders(Borders::ALL).title("Threads"));
+    frame.render_widget(threads, middle[0]);
+
+    let miner_rows = stats.miner_status.values().map(|m| Row::new(vec![
+        format!("{}", m.id),
+        m.pubkey.clone(),
+        m.state.to_string(),
+        m.nonce.map(|n| n.to_string()).unwrap_or_default(),
+        format!("{}", m.total_hashes),
+        format!("{:.4}", m.total_rewards as f64 / ORE_DECIMALS as f64),
+        m.last_landed
+            .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+            .map(|t| t.format("%H:%M:%S").to_string())
+            .unwrap_or_default(),
+    ])).collect::<Vec<_>>();
+    let selected = app.miners.selected().unwrap_or(0).min(miner_rows.len().saturating_sub(1));
+    app.miners.select(Some(selected));
+    let miners = Table::new(miner_rows, [
+        Constraint::Length(4), Constraint::Min(44), Constraint::Length(9), Constraint::Length(12),
+        Constraint::Length(8), Constraint::Length(10), Constraint::Length(9),
+    ])
+        .header(header(&["id", "pubkey", "state", "nonce", "hashes", "rewards", "landed"]))
+        .highlight_style(Style::default().add_modifier(if app.focus == Focus::Miners { Modifier::REVERSED } else { Modifier::BOLD }))
+        .block(Block::default().borders(Borders::ALL).title(format!("Miners ({})", stats.miner_status.len())));
+    frame.render_stateful_widget(miners, middle[1], &mut app.miners);
+
+    // notifications, newest last
+    let filter = app.filter.to_lowercase();
+    let items = stats.notifications.iter()
+        .filter(|n| n.level <= app.level)
+        .filter(|n| filter.is_empty() || n.message.to_lowercase().contains(&filter))
+        .map(|n| {
+            let time = chrono::DateTime::from_timestamp(n.time, 0)
+                .map(|t| t.format("%H:%M:%S").to_string())
+                .unwrap_or_default();
+            let level = match n.level {
+                Level::Error => Span::styled(format!("{:<5}", n.level), Style::default().fg(Color::Red)),
+                Level::Warn => Span::styled(format!("{:<5}", n.level), Style::default().fg(Color::Yellow)),
+                _ => Span::raw(format!("{:<5}", n.level)),
+            };
+            ListItem::new(Line::from(vec![Span::raw(format!("{} ", time)), level, Span::raw(format!(" {}", n.message))]))
+        })
+        .collect::<Vec<_>>();
+    let last = items.len().saturating_sub(1);
+    let selected = match app.log.selected() {
+        Some(s) if app.focus == Focus::Log => s.min(last),
+        _ => last,
+    };
+    app.log.select(Some(selected));
+    let title = if app.editing_filter || !app.filter.is_empty() {
+        format!("Notifications >= {} [filter: {}{}]", app.level, app.filter, if app.editing_filter { "_" } else { "" })
+    } else {
+        format!("Notifications >= {}", app.level)
+    };
+    let log = List::new(items)
+        .highlight_style(Style::default().add_modifier(if app.focus == Focus::Log { Modifier::REVERSED } else { Modifier::empty() }))
+        .block(Block::default().borders(Borders::ALL).title(title));
+    frame.render_stateful_widget(log, rows[4], &mut app.log);
+
+    let status = if stats.paused {
+        Span::styled(" PAUSED ", Style::default().fg(Color::Black).bg(Color::Yellow))
+    } else if !stats.over_budget.is_empty() {
+        Span::styled(" OVER BUDGET ", Style::default().fg(Color::Black).bg(Color::Yellow))
+    } else if stats.unprofitable {
+        Span::styled(" UNPROFITABLE ", Style::default().fg(Color::Black).bg(Color::Yellow))
+    } else {
+        Span::styled(" RUNNING ", Style::default().fg(Color::Black).bg(Color::Green))
+    };
+    let now = chrono::Utc::now().timestamp();
+    let budget = match stats.budget.remaining(now, BUDGET_HOURLY, BUDGET_DAILY) {
+        (None, None) => String::new(),
+        (hourly, daily) => {
+            let sol = |left: Option<u64>| left.map_or("-".to_string(), |l| format!("{:.4}", l as f64 / LAMPORTS_PER_SOL as f64));
+            format!(" budget left {} h / {} d SOL ", sol(hourly), sol(daily))
+        }
+    };
+    let help = Line::from(vec![
+        status,
+        Span::raw(budget),
+        Span::raw(" q quit  p pause/resume  c claim  +/- tip  / filter  l level  tab switch pane  ↑↓ scroll"),
+    ]);
+    frame.render_widget(Paragraph::new(help), rows[5]);
+}
@@ -6,76 +6,95 @@ use ore::{
     MINT_ADDRESS, PROOF, TREASURY_ADDRESS,
 };
 
-use oreprog::{anchor_lang::{ToAccountMetas, InstructionData}, IndexedSolution};
-use solana_client::{nonblocking::rpc_client::RpcClient, client_error::Result, rpc_config::RpcSendTransactionConfig};
-use solana_program::{pubkey::Pubkey, sysvar, instruction::Instruction, address_lookup_table::{state::AddressLookupTable, AddressLookupTableAccount}, message::{VersionedMessage, v0}};
-use solana_sdk::{clock::Clock, commitment_config::{CommitmentConfig, CommitmentLevel}, account::Account, signature::Keypair, pubkey, transaction::VersionedTransaction};
+use oreprog::{anchor_lang::{ToAccountMetas, InstructionData}, constants::{MINER_COLLECTIVE_TREASURY, ORE_PROGRAM_ID}, IndexedSolution};
+use solana_client::{nonblocking::rpc_client::RpcClient, client_error::{ClientError, ClientErrorKind, Result}, rpc_config::{RpcSendTransactionConfig, RpcTransactionConfig}};
+use solana_program::{hash::Hash, pubkey::Pubkey, system_program, instruction::Instruction, address_lookup_table::{state::AddressLookupTable, AddressLookupTableAccount}, message::{VersionedMessage, v0}};
+use solana_sdk::{commitment_config::{CommitmentConfig, CommitmentLevel}, signature::{Keypair, Signature}, transaction::VersionedTransaction};
+use solana_transaction_status::UiTransactionEncoding;
+use std::time::Duration;
 use spl_associated_token_account::get_associated_token_address;
 
-use crate::config::{MINT, ORE_COLLECTIVE};
-pub async fn get_treasury(cluster: String) -> Treasury {
+use crate::config::{MINT, ORE_COLLECTIVE, LAMPORTS_PER_SIGNATURE};
+pub async fn get_treasury(cluster: String) -> Result<Treasury> {
     let client = RpcClient::new_with_commitment(cluster, CommitmentConfig::confirmed());
-    let data = client
-        .get_account_data(&TREASURY_ADDRESS)
-        .await
-        .expect("Failed to get treasury account");
-    *Treasury::try_from_bytes(&data).expect("Failed to parse treasury account")
+    let data = client.get_account_data(&TREASURY_ADDRESS).await?;
+    Treasury::try_from_bytes(&data)
+        .copied()
+        .map_err(|err| custom_error(format!("can't parse the treasury account: {:?}", err)))
 }
 
-pub async fn get_proof(cluster: String, authority: Pubkey) -> Proof {
+pub async fn get_proof(cluster: String, authority: Pubkey) -> Result<Proof> {
     let client = RpcClient::new_with_commitment(cluster, CommitmentConfig::confirmed());
     let proof_address = proof_pubkey(authority);
-    let data = client
-        .get_account_data(&proof_address)
-        .await
-        .expect("Failed to get miner account");
-    *Proof::try_from_bytes(&data).expect("Failed to parse miner account")
+    let data = client.get_account_data(&proof_address).await?;
+    Proof::try_from_bytes(&data)
+        .copied()
+        .map_err(|err| custom_error(format!("can't parse proof {}: {:?}", proof_address, err)))
 }
 
-pub async fn get_account_data(cluster: String, account: Pubkey) -> Account{
+pub async fn get_account_balance(cluster: String, account: Pubkey) -> Result<u64> {
     let client = RpcClient::new_with_commitment(cluster, CommitmentConfig::confirmed());
-    let data = client.get_account(&account).await.expect("failed to get miner account");
-    data
+    client.get_balance(&account).await
 }
 
-pub async fn get_account_balance(cluster: String, account: Pubkey) -> u64 {
+pub async fn get_supply(cluster: String) -> Result<f64> {
     let client = RpcClient::new_with_commitment(cluster, CommitmentConfig::confirmed());
-    let data = client.get_balance(&account).await.expect("failed to get miner account");
-    data
+    let data = client.get_token_supply(&MINT).await?;
+    data.ui_amount.ok_or_else(|| custom_error("token supply without a ui amount".to_string()))
 }
 
-pub async fn get_supply(cluster: String) -> f64 {
+/// The proof of a miner, None if it isn't registered yet
+pub async fn get_state(cluster: String, authority: &Pubkey) -> Result<Option<Proof>> {
     let client = RpcClient::new_with_commitment(cluster, CommitmentConfig::confirmed());
-    let data = client.get_token_supply(&MINT).await.expect("failed to get token supply");
-    data.ui_amount.unwrap()
+    let proof_address = proof_pubkey(*authority);
+    let Some(account) = client
+        .get_account_with_commitment(&proof_address, CommitmentConfig::confirmed())
+        .await?
+        .value else {
+        return Ok(None);
+    };
+    Proof::try_from_bytes(&account.data)
+        .map(|proof| Some(*proof))
+        .map_err(|err| custom_error(format!("can't parse proof {}: {:?}", proof_address, err)))
 }
 
-pub async fn get_state(cluster: String, authority: &Pubkey) -> Option<Proof> {
-    let client = RpcClient::new_with_commitment(cluster, CommitmentConfig::confirmed());
-    let proof_address = proof_pubkey(authority.clone());
-    let data_opt = client
-        .get_account_data(&proof_address)
-        .await
-        .ok();
-    match data_opt {
-        Some(data) => {
-            Some(*Proof::try_from_bytes(&data).expect("Failed to parse miner account"))
-        }
-        None => None
+/// Slot, fee and signature count of a confirmed transaction
+pub struct TxMeta {
+    pub slot: u64,
+    pub fee: u64,
+    pub signatures: u64,
+}
+
+impl TxMeta {
+    /// The part of the fee above the base fee per signature
+    pub fn priority_fee(&self) -> u64 {
+        self.fee.saturating_sub(self.signatures * LAMPORTS_PER_SIGNATURE)
     }
 }
 
-pub async fn get_clock_account(cluster: String) -> Clock {
+/// Fetch the meta of a confirmed transaction. Retries a few times, as the transaction may not be
+/// queryable right after confirmation.
+pub async fn get_transaction_meta(cluster: String, sig: &Signature) -> Option<TxMeta> {
     let client = RpcClient::new_with_commitment(cluster, CommitmentConfig::confirmed());
-    let data = client
-        .get_account_data(&sysvar::clock::ID)
-        .await
-        .expect("Failed to get miner account");
-    bincode::deserialize::<Clock>(&data).expect("Failed to deserialize clock")
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        commitment: Some(CommitmentConfig::confirmed()),
+        max_supported_transaction_version: Some(0),
+    };
+    for _ in 0..5 {
+        if let Ok(tx) = client.get_transaction_with_config(sig, config).await {
+            let signatures = tx.transaction.transaction.decode()
+                .map(|t| t.signatures.len() as u64)
+                .unwrap_or(1);
+            return tx.transaction.meta.map(|meta| TxMeta { slot: tx.slot, fee: meta.fee, signatures });
+        }
+        tokio::time::sleep(Duration::from_millis(2000)).await;
+    }
+    None
 }
 
 pub fn pair_pubkey(k: &Keypair) -> Pubkey {
-    let mut kb: [u8; 32] = [0 as u8;32];
+    let mut kb: [u8; 32] = [0_u8;32];
     kb.copy_from_slice(&k.to_bytes()[32..]);
     Pubkey::new_from_array(kb)
 }
@@ -94,6 +113,36 @@ pub fn treasury_tokens_pubkey() -> Pubkey {
     get_associated_token_address(&TREASURY_ADDRESS, &MINT_ADDRESS)
 }
 
+/// Compile a v0 message paid by `payer`, looking up accounts in the given table if any
+pub async fn compile_message(
+    client: &RpcClient,
+    instructions: &[Instruction],
+    address_lookup_table_key: Option<Pubkey>,
+    payer: &Pubkey,
+    blockhash: Hash,
+) -> Result<VersionedMessage> {
+    let mut address_lookup_table_accounts = vec![];
+    if let Some(address_lookup_table_key) = address_lookup_table_key {
+        let raw_account = client.get_account(&address_lookup_table_key).await?;
+        let address_lookup_table = AddressLookupTable::deserialize(&raw_account.data)
+            .map_err(|err| custom_error(format!("{} is not a lookup table: {}", address_lookup_table_key, err)))?;
+        address_lookup_table_accounts.push(AddressLookupTableAccount {
+            key: address_lookup_table_key,
+            addresses: address_lookup_table.addresses.to_vec(),
+        });
+    }
+    Ok(VersionedMessage::V0(v0::Message::try_compile(
+        payer,
+        instructions,
+        &address_lookup_table_accounts,
+        blockhash,
+    ).map_err(|err| custom_error(format!("can't compile message: {}", err)))?))
+}
+
+pub(crate) fn custom_error(message: String) -> ClientError {
+    ClientErrorKind::Custom(message).into()
+}
+
 pub async fn create_tx_with_address_table_lookup(
     client: &RpcClient,
     instructions: &[Instruction],
@@ -101,23 +150,9 @@ pub async fn create_tx_with_address_table_lookup(
     payer: &Keypair,
     signers: &[&Keypair],
 ) -> Result<(VersionedTransaction, RpcSendTransactionConfig)> {
-    let raw_account = client.get_account(&address_lookup_table_key).await?;
-    let address_lookup_table = AddressLookupTable::deserialize(&raw_account.data).unwrap();
-    let address_lookup_table_account = AddressLookupTableAccount {
-        key: address_lookup_table_key,
-        addresses: address_lookup_table.addresses.to_vec(),
-    };
-
-    let (hash, slot) = client.get_latest_blockhash_with_commitment(CommitmentConfig::confirmed()).await.unwrap();
-    let tx = VersionedTransaction::try_new(
-        VersionedMessage::V0(v0::Message::try_compile(
-            &pair_pubkey(&payer),
-            instructions,
-            &[address_lookup_table_account],
-            hash,
-        ).unwrap()),
-        signers,
-    )?;
+    let (hash, slot) = client.get_latest_blockhash_with_commitment(CommitmentConfig::confirmed()).await?;
+    let message = compile_message(client, instructions, Some(address_lookup_table_key), &pair_pubkey(payer), hash).await?;
+    let tx = VersionedTransaction::try_new(message, signers)?;
     let send_cfg = RpcSendTransactionConfig {
         skip_preflight: true,
         preflight_commitment: Some(CommitmentLevel::Confirmed),
@@ -126,7 +161,7 @@ pub async fn create_tx_with_address_table_lookup(
         min_context_slot: Some(slot),
     };
 
-    assert!(tx.message.address_table_lookups().unwrap().len() > 0);
+    assert!(!tx.message.address_table_lookups().unwrap().is_empty());
     Ok((tx, send_cfg))
 }
 
@@ -140,6 +175,49 @@ pub fn register_ix(
     }
 }
 
+pub fn register_with_payer_ix(
+    accounts: oreprog::accounts::RegisterWithPayer,
+    id: u8) -> Instruction {
+    Instruction { 
+        program_id: ORE_COLLECTIVE, 
+        accounts: accounts.to_account_metas(Some(false)), 
+        data: oreprog::instruction::RegisterWithPayer{ id }.data() 
+    }
+}
+
+/// Register instruction for a miner of `authority`, with a separate `payer` if it isn't the authority
+pub fn registration_ix(authority: Pubkey, payer: Pubkey, miner: Pubkey, id: u8) -> Instruction {
+    if payer == authority {
+        register_ix(oreprog::accounts::Register {
+            miner,
+            proof: proof_pubkey(miner),
+            authority,
+            miner_collective_treasury: MINER_COLLECTIVE_TREASURY,
+            ore: ORE_PROGRAM_ID,
+            system_program: system_program::id(),
+        }, id)
+    } else {
+        register_with_payer_ix(oreprog::accounts::RegisterWithPayer {
+            miner,
+            proof: proof_pubkey(miner),
+            authority,
+            payer,
+            miner_collective_treasury: MINER_COLLECTIVE_TREASURY,
+            ore: ORE_PROGRAM_ID,
+            system_program: system_program::id(),
+        }, id)
+    }
+}
+
+/// Keypairs signing a transaction of the authority paid by `payer`
+pub fn signers<'a>(authority: &'a Keypair, payer: &'a Keypair) -> Vec<&'a Keypair> {
+    if pair_pubkey(authority) == pair_pubkey(payer) {
+        vec![authority]
+    } else {
+        vec![authority, payer]
+    }
+}
+
 pub fn mine_ix(
     accounts: oreprog::accounts::Mine,
     ids: Vec<IndexedSolution>) -> Instruction {